@@ -0,0 +1,151 @@
+use anyhow::Context;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use zip::ZipArchive;
+
+/// Source of the skeleton data, atlas text and page images of a render.
+///
+/// Paths are the ones given on the command line, and for pages the atlas directory joined with
+/// the page name, always with `/` separators when they come from the atlas.
+pub trait AssetResolver {
+    fn read(&self, path: &str) -> anyhow::Result<Vec<u8>>;
+
+    /// Last modification time of `path`, for resolvers reading files that can change while the
+    /// viewer is open. `None` when unknown, and then the file is never reloaded.
+    fn modified(&self, _path: &str) -> Option<SystemTime> {
+        None
+    }
+}
+
+/// Reads assets from disk, relative to `root`.
+#[derive(Debug, Default)]
+pub struct FileSystem {
+    pub root: PathBuf,
+}
+
+impl AssetResolver for FileSystem {
+    fn read(&self, path: &str) -> anyhow::Result<Vec<u8>> {
+        let path = self.root.join(path);
+        std::fs::read(&path).with_context(|| format!("failed to read {}", path.display()))
+    }
+
+    fn modified(&self, path: &str) -> Option<SystemTime> {
+        std::fs::metadata(self.root.join(path))
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+}
+
+/// Serves `-` from the bytes read on stdin, e.g. for `--json -`, and every other path from
+/// `inner`.
+pub struct WithStdin {
+    pub stdin: Vec<u8>,
+    pub inner: &'static dyn AssetResolver,
+}
+
+impl AssetResolver for WithStdin {
+    fn read(&self, path: &str) -> anyhow::Result<Vec<u8>> {
+        match path {
+            "-" => Ok(self.stdin.clone()),
+            path => self.inner.read(path),
+        }
+    }
+
+    fn modified(&self, path: &str) -> Option<SystemTime> {
+        match path {
+            "-" => None,
+            path => self.inner.modified(path),
+        }
+    }
+}
+
+/// Reads assets from a zip archive without extracting it.
+pub struct ZipResolver {
+    archive: Mutex<ZipArchive<File>>,
+}
+
+impl ZipResolver {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        let archive = ZipArchive::new(file)
+            .with_context(|| format!("failed to read zip archive {}", path.display()))?;
+        Ok(Self {
+            archive: Mutex::new(archive),
+        })
+    }
+
+    /// Finds the only file of the archive with one of `extensions`, for bundles where the
+    /// skeleton and atlas paths are not given. When several match, the one named `stem` wins.
+    pub fn locate(
+        &self,
+        extensions: &[&str],
+        stem: Option<&str>,
+        what: &str,
+    ) -> anyhow::Result<String> {
+        let archive = self.archive.lock().unwrap();
+        let candidates: Vec<&str> = archive
+            .file_names()
+            // macOS archivers add resource forks under __MACOSX
+            .filter(|name| !name.starts_with("__MACOSX/"))
+            .filter(|name| extensions.iter().any(|ext| name.ends_with(ext)))
+            .collect();
+        let named = |name: &str| {
+            let file_name = name.rsplit('/').next().unwrap_or(name);
+            stem.is_some_and(|stem| file_name.split('.').next() == Some(stem))
+        };
+        match candidates[..] {
+            [] => anyhow::bail!("no {what} ({}) in bundle", extensions.join(", ")),
+            [name] => Ok(name.to_owned()),
+            _ => match candidates
+                .iter()
+                .filter(|name| named(name))
+                .collect::<Vec<_>>()[..]
+            {
+                [name] => Ok((*name).to_owned()),
+                _ => anyhow::bail!(
+                    "several {what} files in bundle, pick one: {}",
+                    candidates.join(", ")
+                ),
+            },
+        }
+    }
+}
+
+impl AssetResolver for ZipResolver {
+    fn read(&self, path: &str) -> anyhow::Result<Vec<u8>> {
+        let mut archive = self.archive.lock().unwrap();
+        let mut file = archive
+            .by_name(&normalize(path))
+            .with_context(|| format!("no {path} in zip archive"))?;
+        let mut bytes = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// Turns a path into the `/` separated, `.` and `..` free form used as key by archives.
+fn normalize(path: &str) -> String {
+    let mut components: Vec<&str> = vec![];
+    for component in path.split(['/', '\\']) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    components.join("/")
+}
+
+/// Directory of `path`, used by the atlas to locate its pages.
+pub fn parent_dir(path: &str) -> &str {
+    Path::new(path)
+        .parent()
+        .and_then(Path::to_str)
+        .unwrap_or_default()
+}
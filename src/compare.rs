@@ -0,0 +1,150 @@
+use anyhow::Context;
+use clap::ValueEnum;
+use image::{Rgba, RgbaImage};
+use std::path::PathBuf;
+
+use crate::output::srgb_to_linear;
+
+/// How far apart two pixels are, for `compare`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Metric {
+    /// Largest difference between the RGBA channels, from 0 to 255
+    Channel,
+    /// CIE76 color distance (ΔE) of the pixels shown over black and over white, the larger of
+    /// the two; about 2.3 is just noticeable
+    DeltaE,
+}
+
+/// Reference image of `compare` and the differences it tolerates.
+#[derive(Debug)]
+pub struct Comparison {
+    pub reference_path: PathBuf,
+    pub reference: RgbaImage,
+    pub metric: Metric,
+    /// Largest distance of a pixel that still matches, in the unit of the metric.
+    pub tolerance: f32,
+    /// Number of pixels that may exceed the tolerance.
+    pub max_diff_pixels: u64,
+    /// Destination of the heatmap PNG.
+    pub diff_path: Option<PathBuf>,
+}
+
+impl Comparison {
+    /// Compares the rendered `image` with the reference, prints the outcome and writes the
+    /// heatmap. Returns whether they match.
+    pub fn run(&self, image: &RgbaImage) -> anyhow::Result<bool> {
+        let reference = self.reference_path.display();
+        if image.dimensions() != self.reference.dimensions() {
+            eprintln!(
+                "❌ Rendered {}x{} but {reference} is {}x{}",
+                image.width(),
+                image.height(),
+                self.reference.width(),
+                self.reference.height()
+            );
+            return Ok(false);
+        }
+
+        let distances = distances(self.metric, image, &self.reference);
+        let differing = distances
+            .iter()
+            .filter(|&&distance| distance > self.tolerance)
+            .count() as u64;
+        let max_distance = distances.iter().copied().fold(0., f32::max);
+        if let Some(diff_path) = &self.diff_path {
+            heatmap(&self.reference, &distances, self.tolerance)
+                .save(diff_path)
+                .with_context(|| format!("failed to write {}", diff_path.display()))?;
+        }
+
+        let matches = differing <= self.max_diff_pixels;
+        eprintln!(
+            "{} {differing} of {} pixels differ from {reference} by more than {} ({:.3} %), \
+             largest difference {max_distance:.1}",
+            if matches { "✅" } else { "❌" },
+            distances.len(),
+            self.tolerance,
+            differing as f64 * 100. / distances.len() as f64,
+        );
+        Ok(matches)
+    }
+}
+
+/// Distance of each pixel of `image` to the same pixel of `reference`, which has the same size.
+pub fn distances(metric: Metric, image: &RgbaImage, reference: &RgbaImage) -> Vec<f32> {
+    image
+        .pixels()
+        .zip(reference.pixels())
+        .map(|(&pixel, &reference)| metric.distance(pixel, reference))
+        .collect()
+}
+
+/// The reference faded to light gray, with the pixels over the tolerance going from yellow to
+/// red as their distance grows.
+pub fn heatmap(reference: &RgbaImage, distances: &[f32], tolerance: f32) -> RgbaImage {
+    let max_distance = distances.iter().copied().fold(0., f32::max);
+    let width = reference.width();
+    RgbaImage::from_fn(width, reference.height(), |x, y| {
+        let distance = distances[(y * width + x) as usize];
+        if distance > tolerance {
+            let heat = match max_distance > tolerance {
+                true => (distance - tolerance) / (max_distance - tolerance),
+                false => 1.,
+            };
+            return Rgba([255, (255. * (1. - heat)).round() as u8, 0, 255]);
+        }
+        let [r, g, b, a] = reference.get_pixel(x, y).0.map(|channel| channel as f32);
+        let luma = (0.299 * r + 0.587 * g + 0.114 * b) * a / 255. + (255. - a);
+        let faded = (255. - (255. - luma) * 0.35).round() as u8;
+        Rgba([faded, faded, faded, 255])
+    })
+}
+
+impl Metric {
+    fn distance(self, a: Rgba<u8>, b: Rgba<u8>) -> f32 {
+        match self {
+            Metric::Channel => {
+                // the color of a fully transparent pixel does not matter
+                let visible = |pixel: Rgba<u8>| match pixel.0[3] {
+                    0 => [0; 4],
+                    _ => pixel.0,
+                };
+                visible(a)
+                    .into_iter()
+                    .zip(visible(b))
+                    .map(|(a, b)| a.abs_diff(b))
+                    .max()
+                    .unwrap_or(0) as f32
+            }
+            Metric::DeltaE => [0., 1.]
+                .map(|background| {
+                    let [l1, a1, b1] = lab(a, background);
+                    let [l2, a2, b2] = lab(b, background);
+                    ((l1 - l2).powi(2) + (a1 - a2).powi(2) + (b1 - b2).powi(2)).sqrt()
+                })
+                .into_iter()
+                .fold(0., f32::max),
+        }
+    }
+}
+
+/// CIELAB color of a straight alpha sRGB `pixel` composited over a gray `background`, from 0
+/// (black) to 1 (white).
+fn lab(pixel: Rgba<u8>, background: f32) -> [f32; 3] {
+    let alpha = pixel.0[3] as f32 / 255.;
+    let [r, g, b] = [pixel.0[0], pixel.0[1], pixel.0[2]]
+        .map(|channel| srgb_to_linear(channel as f32 / 255. * alpha + background * (1. - alpha)));
+    // XYZ relative to the D65 white point
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let f = |t: f32| {
+        const DELTA: f32 = 6. / 29.;
+        match t > DELTA.powi(3) {
+            true => t.cbrt(),
+            false => t / (3. * DELTA * DELTA) + 4. / 29.,
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116. * fy - 16., 500. * (fx - fy), 200. * (fy - fz)]
+}
@@ -0,0 +1,99 @@
+use anyhow::Context;
+use clap::ValueEnum;
+use glam::Vec2;
+use image::RgbaImage;
+use std::path::Path;
+
+/// How a [`CanvasImage`] is laid over the canvas.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ImageFit {
+    /// Scaled to be fully visible, centered, keeping its aspect ratio
+    #[default]
+    Fit,
+    /// Scaled to cover the whole canvas, centered, keeping its aspect ratio and cropping the rest
+    Fill,
+    /// Repeated at its own size from the top left corner
+    Tile,
+}
+
+/// Static image drawn under or over the skeletons, e.g. card art or a banner frame.
+pub struct CanvasImage {
+    pub image: RgbaImage,
+    pub fit: ImageFit,
+}
+
+/// `--background-image` and `--overlay-image` of a render.
+#[derive(Clone, Copy, Default)]
+pub struct CanvasLayers {
+    pub background: Option<&'static CanvasImage>,
+    pub overlay: Option<&'static CanvasImage>,
+}
+
+/// A textured quad in canvas pixels from the center, y up, corners in winding order starting
+/// from the top left one.
+pub struct ImageQuad {
+    pub corners: [Vec2; 4],
+    pub uvs: [Vec2; 4],
+}
+
+impl CanvasImage {
+    pub fn open(path: &Path, fit: ImageFit) -> anyhow::Result<Self> {
+        let image =
+            image::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        Ok(Self {
+            image: image.to_rgba8(),
+            fit,
+        })
+    }
+
+    /// Quads drawing the image over a canvas of `canvas_size` pixels.
+    pub fn quads(&self, canvas_size: Vec2) -> Vec<ImageQuad> {
+        let image_size = Vec2::new(self.image.width() as f32, self.image.height() as f32);
+        match self.fit {
+            ImageFit::Fit | ImageFit::Fill => {
+                let scales = canvas_size / image_size;
+                let scale = match self.fit {
+                    ImageFit::Fit => scales.min_element(),
+                    _ => scales.max_element(),
+                };
+                let half_size = image_size * scale * 0.5;
+                vec![ImageQuad::new(
+                    Vec2::new(-half_size.x, half_size.y),
+                    half_size * 2.,
+                )]
+            }
+            ImageFit::Tile => {
+                let top_left = Vec2::new(-canvas_size.x, canvas_size.y) * 0.5;
+                let tiles = (canvas_size / image_size).ceil();
+                let mut quads = vec![];
+                for row in 0..tiles.y as u32 {
+                    for column in 0..tiles.x as u32 {
+                        let offset = Vec2::new(column as f32, -(row as f32)) * image_size;
+                        quads.push(ImageQuad::new(top_left + offset, image_size));
+                    }
+                }
+                quads
+            }
+        }
+    }
+}
+
+impl ImageQuad {
+    /// The whole image stretched over `size` pixels, `top_left` being its top left corner.
+    fn new(top_left: Vec2, size: Vec2) -> Self {
+        Self {
+            corners: [
+                top_left,
+                top_left + Vec2::new(size.x, 0.),
+                top_left + Vec2::new(size.x, -size.y),
+                top_left + Vec2::new(0., -size.y),
+            ],
+            uvs: [
+                Vec2::new(0., 0.),
+                Vec2::new(1., 0.),
+                Vec2::new(1., 1.),
+                Vec2::new(0., 1.),
+            ],
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use clap::ValueEnum;
+use glam::Vec2;
+use rusty_spine::{c::spVertexAttachment_computeWorldVertices, Color, Skeleton};
+
+/// Overlays that can be drawn on top of the skeleton with `--debug`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DebugLayer {
+    /// Every layer below
+    All,
+    /// Bone lines and joints
+    Bones,
+    /// Region attachment quads
+    Regions,
+    /// Mesh triangles
+    Meshes,
+    /// Mesh hulls
+    Hulls,
+    /// Bounding box attachments
+    BoundingBoxes,
+    /// Clipping polygons
+    Clipping,
+    /// Point attachments
+    Points,
+}
+
+const BONE_COLOR: Color = Color::new_rgba(1., 0.25, 0.25, 1.);
+const JOINT_COLOR: Color = Color::new_rgba(0.1, 0.1, 0.1, 1.);
+const REGION_COLOR: Color = Color::new_rgba(0.3, 0.5, 1., 1.);
+const MESH_COLOR: Color = Color::new_rgba(1., 1., 0.3, 0.6);
+const HULL_COLOR: Color = Color::new_rgba(1., 0.6, 0.1, 1.);
+const BOUNDING_BOX_COLOR: Color = Color::new_rgba(0.2, 1., 0.2, 1.);
+const CLIPPING_COLOR: Color = Color::new_rgba(0.8, 0.2, 1., 1.);
+const POINT_COLOR: Color = Color::new_rgba(0.2, 1., 1., 1.);
+
+/// A flat colored quad in skeleton coordinates, corners in winding order.
+pub struct DebugQuad {
+    pub corners: [Vec2; 4],
+    pub color: Color,
+}
+
+/// Builds the overlay geometry for the current pose of `skeleton`.
+///
+/// `pixel` is the size of one output pixel in skeleton units, so that lines keep a constant
+/// on-screen width whatever the skeleton scale.
+pub fn build_overlay(skeleton: &Skeleton, layers: &[DebugLayer], pixel: f32) -> Vec<DebugQuad> {
+    let enabled = |layer| layers.contains(&DebugLayer::All) || layers.contains(&layer);
+    let mut overlay = Overlay {
+        quads: vec![],
+        line_width: pixel * 1.5,
+    };
+    let mut world_vertices = vec![];
+
+    for slot in skeleton.draw_order() {
+        if !slot.bone().active() {
+            continue;
+        }
+        if let Some(region) = slot
+            .region_attachment()
+            .filter(|_| enabled(DebugLayer::Regions))
+        {
+            world_vertices.resize(8, 0.);
+            unsafe { region.compute_world_vertices(&slot, &mut world_vertices, 0, 2) };
+            overlay.polygon(&to_points(&world_vertices), REGION_COLOR);
+        }
+        if let Some(mesh) = slot.mesh_attachment() {
+            world_vertices.resize(mesh.world_vertices_length() as usize, 0.);
+            unsafe {
+                mesh.compute_world_vertices(
+                    &slot,
+                    0,
+                    mesh.world_vertices_length(),
+                    &mut world_vertices,
+                    0,
+                    2,
+                )
+            };
+            let points = to_points(&world_vertices);
+            if enabled(DebugLayer::Meshes) {
+                let triangles = unsafe {
+                    std::slice::from_raw_parts(mesh.triangles(), mesh.triangles_count() as usize)
+                };
+                for triangle in triangles.chunks_exact(3) {
+                    let corners = triangle.iter().map(|&index| points[index as usize]);
+                    overlay.polygon(&corners.collect::<Vec<_>>(), MESH_COLOR);
+                }
+            }
+            if enabled(DebugLayer::Hulls) {
+                let hull_length = (mesh.hull_length() / 2) as usize;
+                overlay.polygon(&points[..hull_length.min(points.len())], HULL_COLOR);
+            }
+        }
+        if let Some(bounding_box) = slot
+            .bounding_box_attachment()
+            .filter(|_| enabled(DebugLayer::BoundingBoxes))
+        {
+            world_vertices.resize(bounding_box.world_vertices_length() as usize, 0.);
+            unsafe {
+                bounding_box.compute_world_vertices(
+                    &slot,
+                    0,
+                    bounding_box.world_vertices_length(),
+                    &mut world_vertices,
+                    0,
+                    2,
+                )
+            };
+            overlay.polygon(&to_points(&world_vertices), BOUNDING_BOX_COLOR);
+        }
+        if let Some(clipping) = slot
+            .clipping_attachment()
+            .filter(|_| enabled(DebugLayer::Clipping))
+        {
+            // ClippingAttachment does not expose its vertex accessors, go through spine-c directly
+            unsafe {
+                let vertex_attachment = &mut (*clipping.c_ptr()).super_0;
+                let length = vertex_attachment.worldVerticesLength;
+                world_vertices.resize(length as usize, 0.);
+                spVertexAttachment_computeWorldVertices(
+                    vertex_attachment,
+                    slot.c_ptr(),
+                    0,
+                    length,
+                    world_vertices.as_mut_ptr(),
+                    0,
+                    2,
+                );
+            }
+            overlay.polygon(&to_points(&world_vertices), CLIPPING_COLOR);
+        }
+        if let Some(point) = slot
+            .point_attachment()
+            .filter(|_| enabled(DebugLayer::Points))
+        {
+            let (x, y) = point.compute_world_position(&slot.bone());
+            overlay.joint(Vec2::new(x, y), pixel * 4., POINT_COLOR);
+        }
+    }
+
+    if enabled(DebugLayer::Bones) {
+        for bone in skeleton.bones().filter(|bone| bone.active()) {
+            let start = Vec2::new(bone.world_x(), bone.world_y());
+            let length = bone.data().length();
+            if length > 0. {
+                let end = start + Vec2::new(bone.a(), bone.c()) * length;
+                overlay.line(start, end, BONE_COLOR);
+            }
+            overlay.joint(start, pixel * 3., JOINT_COLOR);
+        }
+    }
+
+    overlay.quads
+}
+
+fn to_points(world_vertices: &[f32]) -> Vec<Vec2> {
+    world_vertices
+        .chunks_exact(2)
+        .map(|xy| Vec2::new(xy[0], xy[1]))
+        .collect()
+}
+
+struct Overlay {
+    quads: Vec<DebugQuad>,
+    line_width: f32,
+}
+
+impl Overlay {
+    fn line(&mut self, a: Vec2, b: Vec2, color: Color) {
+        let Some(direction) = (b - a).try_normalize() else {
+            return;
+        };
+        let normal = direction.perp() * self.line_width * 0.5;
+        self.quads.push(DebugQuad {
+            corners: [a - normal, b - normal, b + normal, a + normal],
+            color,
+        });
+    }
+
+    fn polygon(&mut self, points: &[Vec2], color: Color) {
+        for (i, &point) in points.iter().enumerate() {
+            self.line(point, points[(i + 1) % points.len()], color);
+        }
+    }
+
+    fn joint(&mut self, center: Vec2, radius: f32, color: Color) {
+        self.quads.push(DebugQuad {
+            corners: [
+                center + Vec2::new(radius, 0.),
+                center + Vec2::new(0., radius),
+                center + Vec2::new(-radius, 0.),
+                center + Vec2::new(0., -radius),
+            ],
+            color,
+        });
+    }
+}
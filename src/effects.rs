@@ -0,0 +1,227 @@
+use image::{GrayImage, Luma, RgbaImage};
+use imageproc::distance_transform::euclidean_squared_distance_transform;
+use imageproc::filter::gaussian_blur_f32;
+use rusty_spine::Color;
+use std::ops::RangeInclusive;
+
+use crate::scene::HexColor;
+
+/// Stroke around the alpha silhouette of the skeletons.
+#[derive(Clone, Copy, Debug)]
+pub struct Outline {
+    /// In output pixels.
+    pub width: f32,
+    pub color: Color,
+}
+
+/// Blurred copy of the silhouette drawn under it.
+#[derive(Clone, Copy, Debug)]
+pub struct DropShadow {
+    /// In output pixels, y down.
+    pub offset: [f32; 2],
+    /// Blur radius in output pixels, 0 for a hard shadow.
+    pub blur: f32,
+    pub color: Color,
+}
+
+/// Blurred halo around the silhouette.
+#[derive(Clone, Copy, Debug)]
+pub struct Glow {
+    /// In output pixels.
+    pub radius: f32,
+    pub color: Color,
+}
+
+/// Post-processing passes applied to the captured skeletons, before the canvas images.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Effects {
+    pub outline: Option<Outline>,
+    pub drop_shadow: Option<DropShadow>,
+    pub glow: Option<Glow>,
+}
+
+impl Effects {
+    pub fn is_empty(&self) -> bool {
+        self.outline.is_none() && self.drop_shadow.is_none() && self.glow.is_none()
+    }
+
+    /// Applies the effects to `image`, in straight alpha. From bottom to top: drop shadow,
+    /// glow, outline, then the image itself. Shadow and glow follow the outlined silhouette.
+    pub fn apply(&self, image: &RgbaImage) -> RgbaImage {
+        let mut silhouette = GrayImage::from_fn(image.width(), image.height(), |x, y| {
+            Luma([image.get_pixel(x, y).0[3]])
+        });
+        let outline = self.outline.map(|outline| {
+            let stroke = stroke(&silhouette, outline.width);
+            for (pixel, stroke) in silhouette.pixels_mut().zip(stroke.pixels()) {
+                pixel.0[0] = pixel.0[0].max(stroke.0[0]);
+            }
+            (stroke, outline.color)
+        });
+
+        let mut result = RgbaImage::new(image.width(), image.height());
+        if let Some(drop_shadow) = self.drop_shadow {
+            let [dx, dy] = drop_shadow.offset.map(|offset| offset.round() as i64);
+            let shifted = GrayImage::from_fn(image.width(), image.height(), |x, y| {
+                let (x, y) = (x as i64 - dx, y as i64 - dy);
+                match (u32::try_from(x), u32::try_from(y)) {
+                    (Ok(x), Ok(y)) if x < image.width() && y < image.height() => {
+                        *silhouette.get_pixel(x, y)
+                    }
+                    _ => Luma([0]),
+                }
+            });
+            fill_over(
+                &mut result,
+                &blur(&shifted, drop_shadow.blur),
+                drop_shadow.color,
+            );
+        }
+        if let Some(glow) = self.glow {
+            fill_over(&mut result, &blur(&silhouette, glow.radius), glow.color);
+        }
+        if let Some((stroke, color)) = &outline {
+            fill_over(&mut result, stroke, *color);
+        }
+        for (destination, source) in result.pixels_mut().zip(image.pixels()) {
+            destination.0 = over(source.0.map(|channel| channel as f32 / 255.), destination.0);
+        }
+        result
+    }
+}
+
+/// Coverage of a stroke of `width` pixels around the mostly opaque pixels of `alpha`, with an
+/// antialiased outer edge.
+fn stroke(alpha: &GrayImage, width: f32) -> GrayImage {
+    let opaque = GrayImage::from_fn(alpha.width(), alpha.height(), |x, y| {
+        Luma([if alpha.get_pixel(x, y).0[0] >= 128 {
+            255
+        } else {
+            0
+        }])
+    });
+    let distances = euclidean_squared_distance_transform(&opaque);
+    GrayImage::from_fn(alpha.width(), alpha.height(), |x, y| {
+        let distance = distances.get_pixel(x, y).0[0].sqrt() as f32;
+        Luma([((width + 0.5 - distance).clamp(0., 1.) * 255.).round() as u8])
+    })
+}
+
+/// Gaussian blur of `radius` pixels, the image itself when the radius is zero.
+fn blur(mask: &GrayImage, radius: f32) -> GrayImage {
+    match radius > 0. {
+        true => gaussian_blur_f32(mask, radius / 2.),
+        false => mask.clone(),
+    }
+}
+
+/// Draws `color` over `image` wherever `mask` covers it.
+fn fill_over(image: &mut RgbaImage, mask: &GrayImage, color: Color) {
+    for (pixel, coverage) in image.pixels_mut().zip(mask.pixels()) {
+        let alpha = coverage.0[0] as f32 / 255. * color.a;
+        pixel.0 = over([color.r, color.g, color.b, alpha], pixel.0);
+    }
+}
+
+/// Straight alpha "over" operator, `source` being in 0..1.
+fn over(source: [f32; 4], destination: [u8; 4]) -> [u8; 4] {
+    let [sr, sg, sb, sa] = source;
+    let [dr, dg, db, da] = destination.map(|channel| channel as f32 / 255.);
+    let alpha = sa + da * (1. - sa);
+    if alpha <= 0. {
+        return [0; 4];
+    }
+    let blend = |source: f32, destination: f32| {
+        let color = (source * sa + destination * da * (1. - sa)) / alpha;
+        (color * 255.).round().clamp(0., 255.) as u8
+    };
+    [
+        blend(sr, dr),
+        blend(sg, dg),
+        blend(sb, db),
+        (alpha * 255.).round() as u8,
+    ]
+}
+
+/// Parses `--outline WIDTH[,COLOR]`.
+pub fn parse_outline(arg: &str) -> Result<Outline, String> {
+    let (numbers, color) = split_args(
+        arg,
+        "WIDTH[,COLOR]",
+        1..=1,
+        0,
+        Color::new_rgba(0., 0., 0., 1.),
+    )?;
+    Ok(Outline {
+        width: numbers[0],
+        color,
+    })
+}
+
+/// Parses `--drop-shadow DX,DY[,BLUR][,COLOR]`.
+pub fn parse_drop_shadow(arg: &str) -> Result<DropShadow, String> {
+    let (numbers, color) = split_args(
+        arg,
+        "DX,DY[,BLUR][,COLOR]",
+        2..=3,
+        2,
+        Color::new_rgba(0., 0., 0., 0.5),
+    )?;
+    Ok(DropShadow {
+        offset: [numbers[0], numbers[1]],
+        blur: numbers.get(2).copied().unwrap_or(0.),
+        color,
+    })
+}
+
+/// Parses `--glow RADIUS[,COLOR]`.
+pub fn parse_glow(arg: &str) -> Result<Glow, String> {
+    let (numbers, color) = split_args(
+        arg,
+        "RADIUS[,COLOR]",
+        1..=1,
+        0,
+        Color::new_rgba(1., 1., 1., 1.),
+    )?;
+    Ok(Glow {
+        radius: numbers[0],
+        color,
+    })
+}
+
+/// Splits comma separated numbers, as many as `count`, followed by an optional `#rrggbb[aa]`
+/// color defaulting to `color`. `usage` describes the expected value in errors. Numbers from
+/// `sizes_from` on are sizes, which must not be negative; the ones before are offsets.
+fn split_args(
+    arg: &str,
+    usage: &str,
+    count: RangeInclusive<usize>,
+    sizes_from: usize,
+    color: Color,
+) -> Result<(Vec<f32>, Color), String> {
+    let mut numbers = vec![];
+    let mut parsed_color = None;
+    for part in arg.split(',').map(str::trim) {
+        if parsed_color.is_some() {
+            return Err(format!("the color must come last in `{arg}`"));
+        }
+        if part.starts_with('#') {
+            parsed_color = Some(HexColor::try_from(part.to_owned())?.0);
+        } else {
+            let number: f32 = part
+                .parse()
+                .map_err(|_| format!("invalid number `{part}` in `{arg}`"))?;
+            if !number.is_finite() {
+                return Err(format!("invalid number `{part}` in `{arg}`"));
+            }
+            numbers.push(number);
+        }
+    }
+    if !count.contains(&numbers.len()) {
+        return Err(format!("expected {usage}, got `{arg}`"));
+    }
+    if numbers[sizes_from..].iter().any(|&size| size < 0.) {
+        return Err(format!("sizes must not be negative in `{arg}`"));
+    }
+    Ok((numbers, parsed_color.unwrap_or(color)))
+}
@@ -0,0 +1,106 @@
+use glam::{Mat4, Vec2};
+use image::{Rgba, RgbaImage};
+use imageproc::{drawing::draw_polygon_mut, point::Point};
+use rusty_spine::Skeleton;
+use serde::Serialize;
+
+use crate::palette::id_color;
+use crate::report::project_to_image;
+
+/// One bounding box attachment evaluated at the rendered pose.
+#[derive(Debug, Serialize)]
+pub struct HitRegion {
+    /// Index of the skeleton in the scene, in drawing order.
+    pub skeleton: usize,
+    pub slot: String,
+    pub attachment: String,
+    /// Color of this region in the mask PNG.
+    pub color: [u8; 3],
+    /// Polygon in image coordinates (origin top-left, y down).
+    pub polygon: Vec<[f32; 2]>,
+}
+
+/// Hit areas of a rendered frame, written as JSON for UI engines.
+#[derive(Debug, Serialize)]
+pub struct HitMap {
+    pub width: u32,
+    pub height: u32,
+    pub regions: Vec<HitRegion>,
+}
+
+impl HitMap {
+    /// Evaluates every active bounding box attachment of the skeletons and projects it into an
+    /// image of `width` x `height` pixels using each skeleton's `view * world` matrix.
+    pub fn evaluate<'a>(
+        skeletons: impl IntoIterator<Item = (&'a Skeleton, Mat4)>,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let image_size = Vec2::new(width as f32, height as f32);
+        let mut regions = vec![];
+        let mut world_vertices = vec![];
+        for (skeleton_index, (skeleton, view_world)) in skeletons.into_iter().enumerate() {
+            for slot in skeleton.draw_order() {
+                if !slot.bone().active() {
+                    continue;
+                }
+                let Some(bounding_box) = slot.bounding_box_attachment() else {
+                    continue;
+                };
+                let length = bounding_box.world_vertices_length();
+                world_vertices.resize(length as usize, 0.);
+                unsafe {
+                    bounding_box.compute_world_vertices(&slot, 0, length, &mut world_vertices, 0, 2)
+                };
+                regions.push(HitRegion {
+                    skeleton: skeleton_index,
+                    slot: slot.data().name().to_owned(),
+                    attachment: bounding_box.name().to_owned(),
+                    color: id_color(regions.len()),
+                    polygon: world_vertices
+                        .chunks_exact(2)
+                        .map(|xy| {
+                            project_to_image(view_world, image_size, Vec2::new(xy[0], xy[1])).into()
+                        })
+                        .collect(),
+                });
+            }
+        }
+        Self {
+            width,
+            height,
+            regions,
+        }
+    }
+
+    pub fn write_json(&self, path: &str) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(())
+    }
+
+    /// Fills each region with its color on a transparent image. Later regions in draw order
+    /// cover earlier ones, as they would on screen.
+    pub fn write_mask(&self, path: &str) -> anyhow::Result<()> {
+        let mut mask = RgbaImage::new(self.width, self.height);
+        for region in &self.regions {
+            let mut polygon: Vec<Point<i32>> = vec![];
+            for [x, y] in &region.polygon {
+                let point = Point::new(x.round() as i32, y.round() as i32);
+                if polygon.last() != Some(&point) {
+                    polygon.push(point);
+                }
+            }
+            // imageproc expects an open polygon
+            while polygon.len() > 1 && polygon.first() == polygon.last() {
+                polygon.pop();
+            }
+            if polygon.len() < 3 {
+                continue;
+            }
+            let [r, g, b] = region.color;
+            draw_polygon_mut(&mut mask, &polygon, Rgba([r, g, b, 255]));
+        }
+        mask.save(path)?;
+        Ok(())
+    }
+}
@@ -1,17 +1,43 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
-use glam::Vec2;
-use rusty_spine::{
-    atlas::{AtlasFilter, AtlasFormat, AtlasWrap},
-    Atlas, SkeletonJson, Skin,
-};
+use anyhow::{Context as _, Result};
+use clap::{Args, Parser, Subcommand};
+use rusty_spine::{atlas::AtlasWrap, Color};
 use std::{
+    io::Read,
     path::{Path, PathBuf},
     sync::{Arc, Mutex},
+    time::Instant,
 };
+mod assets;
+mod compare;
+mod compose;
+mod debug;
+mod effects;
+mod hitmap;
+mod mask;
+mod metadata;
+mod output;
+mod palette;
+mod reload;
+mod replace;
+mod report;
+mod scene;
+mod service;
+mod skeleton_diff;
 mod spine;
+mod texture;
+mod viewer;
+use assets::{AssetResolver, FileSystem, WithStdin, ZipResolver};
+use compare::{Comparison, Metric};
+use compose::{CanvasImage, CanvasLayers, ImageFit};
+use debug::DebugLayer;
+use effects::{parse_drop_shadow, parse_glow, parse_outline, DropShadow, Effects, Glow, Outline};
+use mask::RenderMode;
 use miniquad::*;
-use spine::{SpineInfo, SpineSkeletonPath, SpineTexture, Stage};
+use output::{AlphaMode, ColorSpaceMode, Encoding, OutputFormat, PngCompression};
+use replace::parse_replacement;
+use scene::{Camera, Canvas, HexColor, Scale, Scene, SkeletonInstance};
+use skeleton_diff::{DiffLayout, SkeletonOutline, StructuralDiff};
+use spine::{read_skeleton_data, LoadOptions, OutputInfo, SpineInfo, SpineTexture, Stage};
 
 // 1. Struct globale du CLI
 #[derive(Parser, Debug)]
@@ -25,145 +51,402 @@ struct Cli {
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Génère une image PNG à partir d'un JSON Spine et d'un atlas
-    Render {
-        /// Chemin vers le fichier Skeleton JSON Spine
-        #[arg(long, value_name = "FILE")]
-        json: PathBuf,
+    Render(Box<RenderArgs>),
+    /// Sert des rendus PNG en HTTP (`GET /render?skeleton=…`), squelettes, atlas et textures
+    /// restant en mémoire entre les requêtes
+    Serve(ServeArgs),
+    /// Lit des rendus en JSON ligne par ligne sur stdin et écrit un résultat JSON par ligne sur
+    /// stdout, squelettes, atlas et textures restant en mémoire entre les rendus
+    Daemon(ServiceArgs),
+    /// Rend comme `render` puis compare l'image à une référence : test de non-régression,
+    /// code de sortie 1 quand elles diffèrent
+    Compare(Box<CompareArgs>),
+    /// Rend deux versions d'un squelette dans les mêmes conditions et liste les os, slots, skins,
+    /// attachments et animations ajoutés ou retirés
+    DiffSkeletons(Box<DiffSkeletonsArgs>),
+}
+
+// 3. Options de rendu
+#[derive(Args, Debug)]
+pub struct RenderArgs {
+    /// Chemin vers le fichier Skeleton JSON Spine (ou binaire .skel), `-` pour lire le JSON
+    /// sur stdin
+    #[arg(long, value_name = "FILE", required_unless_present_any = ["bundle", "scene"])]
+    json: Option<PathBuf>,
+
+    /// Chemin vers le fichier atlas Spine (.atlas)
+    #[arg(long, value_name = "FILE", required_unless_present_any = ["bundle", "scene"])]
+    atlas: Option<PathBuf>,
+
+    /// Export Spine zippé (squelette, atlas et pages) rendu sans extraction ;
+    /// `--json` et `--atlas` deviennent des chemins internes optionnels
+    #[arg(long, value_name = "ZIP", conflicts_with = "assets")]
+    bundle: Option<PathBuf>,
+
+    /// Scène JSON décrivant le canevas (taille, fond, caméra) et un ou plusieurs squelettes
+    /// (skins, pistes d'animation, transformations, slots, teintes) rendus dans la même image
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = [
+            "json", "atlas", "bundle", "base_skin", "skins", "replace_region", "scale", "flip_x",
+            "flip_y",
+        ]
+    )]
+    scene: Option<PathBuf>,
+
+    /// Chemin de sortie pour le PNG généré, `-` pour l'écrire sur stdout
+    #[arg(long, value_name = "FILE", default_value = "none")]
+    out: PathBuf,
+
+    /// Format de l'image écrite, déduit de l'extension de `--out` par défaut (PNG sur stdout)
+    #[arg(long, value_enum)]
+    format: Option<OutputFormat>,
+
+    /// Qualité JPEG (90 par défaut) ou WebP avec perte, de 1 à 100 ; sans elle, le WebP est
+    /// sans perte
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: Option<u8>,
+
+    /// Couleur sur laquelle l'image est aplatie pour les formats sans alpha (JPEG)
+    #[arg(long, value_name = "COLOR", default_value = "#ffffff", value_parser = parse_color)]
+    matte: HexColor,
+
+    /// Niveau de compression des PNG (`best` par défaut avec `--optimize`)
+    #[arg(long, value_enum)]
+    png_compression: Option<PngCompression>,
+
+    /// Réduit les PNG sans perte : palette quand l'image a 256 couleurs ou moins, RGB quand
+    /// elle est opaque, filtres adaptatifs
+    #[arg(long)]
+    optimize: bool,
+
+    /// Intègre au PNG l'origine du rendu : version de l'outil, et pour chaque squelette son
+    /// empreinte SHA-256, son atlas, ses skins, ses animations et le temps rendu
+    #[arg(long)]
+    metadata: bool,
+
+    /// Reste ouvert et régénère les sorties quand le squelette, l'atlas ou une page change
+    #[arg(long)]
+    watch: bool,
+
+    /// Skin de base
+    #[arg(long, default_value = "")]
+    base_skin: String,
+
+    /// Liste de skins additionnels à fusionner (séparés par virgule)
+    #[arg(long, value_delimiter = ',')]
+    skins: Vec<String>,
+
+    /// Écrit un rapport JSON du rendu (dimensions, bornes, skins, atlas…), `-` pour stdout
+    #[arg(long, value_name = "FILE")]
+    report: Option<PathBuf>,
+
+    /// Surcouches de debug à dessiner (séparées par virgule), toutes si aucune n'est précisée
+    #[arg(long, value_delimiter = ',', num_args = 0.., default_missing_value = "all")]
+    debug: Vec<DebugLayer>,
+
+    /// Exporte les bounding boxes de la pose rendue en polygones JSON (coordonnées image)
+    #[arg(long, value_name = "FILE")]
+    hitmap: Option<PathBuf>,
+
+    /// Exporte les bounding boxes en masque PNG, une couleur par zone
+    #[arg(long, value_name = "FILE")]
+    hitmap_mask: Option<PathBuf>,
+
+    /// Mode de rendu : couleurs texturées, ou une couleur unie par slot (masque)
+    #[arg(long, value_enum, default_value_t = RenderMode::Color)]
+    mode: RenderMode,
+
+    /// Écrit l'ID de chaque slot dans une image PNG 16 bits (avec `--mode slot-mask`)
+    #[arg(long, value_name = "FILE")]
+    slot_ids: Option<PathBuf>,
 
-        /// Chemin vers le fichier atlas Spine (.atlas)
-        #[arg(long, value_name = "FILE")]
-        atlas: PathBuf,
+    /// Écrit la correspondance couleur / ID / nom de slot du masque en JSON
+    #[arg(long, value_name = "FILE")]
+    slot_legend: Option<PathBuf>,
 
-        /// Chemin de sortie pour le PNG généré
-        #[arg(long, value_name = "FILE", default_value = "none")]
-        out: PathBuf,
+    #[command(flatten)]
+    color: ColorArgs,
 
-        /// Skin de base
-        #[arg(long, default_value = "")]
-        base_skin: String,
+    /// Lit le squelette, l'atlas et les pages depuis ce dossier ou cette archive zip
+    /// (`--json` et `--atlas` sont alors relatifs à celle-ci)
+    #[arg(long, value_name = "DIR|ZIP")]
+    assets: Option<PathBuf>,
 
-        /// Liste de skins additionnels à fusionner (séparés par virgule)
-        #[arg(long, value_delimiter = ',')]
-        skins: Vec<String>,
-    },
+    /// Remplace l'image d'une région de l'atlas par un PNG externe, étiré à la taille de la
+    /// région (répétable)
+    #[arg(long, value_name = "REGION=FILE", value_parser = parse_replacement)]
+    replace_region: Vec<(String, PathBuf)>,
+
+    /// Image dessinée sous les squelettes, fixe quelle que soit la caméra
+    #[arg(long, value_name = "FILE")]
+    background_image: Option<PathBuf>,
+
+    /// Placement de `--background-image` sur le canevas
+    #[arg(long, value_enum, default_value_t = ImageFit::Fit)]
+    background_fit: ImageFit,
+
+    /// Image dessinée par-dessus les squelettes (cadre, bannière…)
+    #[arg(long, value_name = "FILE")]
+    overlay_image: Option<PathBuf>,
+
+    /// Placement de `--overlay-image` sur le canevas
+    #[arg(long, value_enum, default_value_t = ImageFit::Fit)]
+    overlay_fit: ImageFit,
+
+    /// Contour autour de la silhouette des squelettes : épaisseur en pixels et couleur
+    /// (noir par défaut), ex. `3,#ffffff`
+    #[arg(long, value_name = "WIDTH[,COLOR]", value_parser = parse_outline)]
+    outline: Option<Outline>,
+
+    /// Ombre portée des squelettes : décalage en pixels (y vers le bas), flou et couleur
+    /// (noir à 50 % par défaut), ex. `8,8,6,#00000080`
+    #[arg(
+        long,
+        value_name = "DX,DY[,BLUR][,COLOR]",
+        value_parser = parse_drop_shadow,
+        allow_hyphen_values = true
+    )]
+    drop_shadow: Option<DropShadow>,
+
+    /// Halo autour des squelettes : rayon en pixels et couleur (blanc par défaut)
+    #[arg(long, value_name = "RADIUS[,COLOR]", value_parser = parse_glow)]
+    glow: Option<Glow>,
+
+    /// Échelle du squelette, uniforme ou par axe, ex. `0.5` ou `-1,1`
+    #[arg(long, value_name = "X[,Y]", allow_hyphen_values = true)]
+    scale: Option<Scale>,
+
+    /// Retourne le squelette horizontalement (via son scaleX, pour les variantes gauche/droite)
+    #[arg(long)]
+    flip_x: bool,
+
+    /// Retourne le squelette verticalement (via son scaleY)
+    #[arg(long)]
+    flip_y: bool,
+
+    /// Grossissement de la caméra, remplace celui de la scène
+    #[arg(long)]
+    zoom: Option<f32>,
+
+    /// Point de la scène au centre de l'image, remplace celui de la scène
+    #[arg(long, value_name = "X,Y", value_parser = parse_pan, allow_hyphen_values = true)]
+    pan: Option<[f32; 2]>,
+
+    /// Rotation de la caméra en degrés (sens anti-horaire), remplace celle de la scène
+    #[arg(long, value_name = "DEGREES", allow_negative_numbers = true)]
+    rotation: Option<f32>,
+}
+
+// 4. Options du serveur de rendu
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Adresse d'écoute du serveur HTTP
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+    bind: String,
+
+    #[command(flatten)]
+    service: ServiceArgs,
+}
+
+// 5. Options communes au serveur et au démon
+#[derive(Args, Debug)]
+pub struct ServiceArgs {
+    /// Dossier ou archive zip d'où sont lus les squelettes, atlas et pages demandés
+    #[arg(long, value_name = "DIR|ZIP", default_value = ".")]
+    assets: PathBuf,
+
+    #[command(flatten)]
+    color: ColorArgs,
+}
+
+// 6. Options d'alpha et de couleur communes au rendu, au serveur et au démon
+#[derive(Args, Debug)]
+pub struct ColorArgs {
+    /// Alpha des images écrites ou renvoyées : droit (standard PNG) ou prémultiplié
+    #[arg(long, value_enum, default_value_t = AlphaMode::Straight)]
+    output_alpha: AlphaMode,
+
+    /// Force l'alpha des pages d'atlas quand l'en-tête `pma` est faux (auto-détecté sinon)
+    #[arg(long, value_enum)]
+    atlas_alpha: Option<AlphaMode>,
+
+    /// Espace de mélange des couleurs : sRGB (comme l'éditeur Spine) ou lumière linéaire,
+    /// stockée sur 8 bits par canal (bandes visibles dans les dégradés sombres)
+    #[arg(long, value_enum, default_value_t = ColorSpaceMode::Srgb)]
+    color_space: ColorSpaceMode,
+}
+
+// 7. Options de comparaison à une image de référence
+#[derive(Args, Debug)]
+pub struct CompareArgs {
+    /// Image de référence attendue
+    #[arg(long, value_name = "FILE")]
+    reference: PathBuf,
+
+    /// Mesure de l'écart entre deux pixels : plus grand écart de canal RGBA (0–255) ou
+    /// distance perceptuelle ΔE
+    #[arg(long, value_enum, default_value_t = Metric::Channel)]
+    metric: Metric,
+
+    /// Écart toléré par pixel, dans l'unité de la métrique
+    #[arg(long, default_value_t = 0.)]
+    tolerance: f32,
+
+    /// Nombre de pixels qui peuvent dépasser la tolérance
+    #[arg(long, value_name = "COUNT", default_value_t = 0)]
+    max_diff_pixels: u64,
+
+    /// Écrit une carte de chaleur PNG des pixels hors tolérance
+    #[arg(long, value_name = "FILE")]
+    diff: Option<PathBuf>,
+
+    #[command(flatten)]
+    render: RenderArgs,
+}
+
+// 8. Options de comparaison de deux versions d'un squelette
+#[derive(Args, Debug)]
+pub struct DiffSkeletonsArgs {
+    /// Ancienne version du squelette (JSON ou binaire .skel), relative à `--assets`
+    #[arg(long, value_name = "FILE")]
+    old_json: PathBuf,
+
+    /// Atlas de l'ancienne version, le squelette avec l'extension .atlas par défaut
+    #[arg(long, value_name = "FILE")]
+    old_atlas: Option<PathBuf>,
+
+    /// Nouvelle version du squelette (JSON ou binaire .skel), relative à `--assets`
+    #[arg(long, value_name = "FILE")]
+    new_json: PathBuf,
+
+    /// Atlas de la nouvelle version, le squelette avec l'extension .atlas par défaut
+    #[arg(long, value_name = "FILE")]
+    new_atlas: Option<PathBuf>,
+
+    /// Skins à fusionner (séparés par virgule), le premier servant de base
+    #[arg(long, value_delimiter = ',')]
+    skins: Vec<String>,
+
+    /// Animation rendue, la pose de setup sinon
+    #[arg(long)]
+    animation: Option<String>,
+
+    /// Temps de l'animation en secondes
+    #[arg(long, value_name = "SECONDS", default_value_t = 0.)]
+    time: f32,
+
+    /// Largeur de chaque rendu en pixels
+    #[arg(long, default_value_t = Canvas::default().width)]
+    width: u32,
+
+    /// Hauteur de chaque rendu en pixels
+    #[arg(long, default_value_t = Canvas::default().height)]
+    height: u32,
+
+    /// Grossissement de la caméra
+    #[arg(long, default_value_t = 1.)]
+    zoom: f32,
+
+    /// Couleur de fond `#rrggbb[aa]`, transparent par défaut
+    #[arg(long, value_name = "COLOR", value_parser = parse_color)]
+    background: Option<HexColor>,
+
+    /// Image PNG des deux rendus ; sans elle, seule la structure est comparée
+    #[arg(long, value_name = "FILE")]
+    out: Option<PathBuf>,
+
+    /// Disposition des deux rendus dans l'image
+    #[arg(long, value_enum, default_value_t = DiffLayout::SideBySide)]
+    layout: DiffLayout,
+
+    /// Écrit les différences de structure en JSON, `-` pour stdout
+    #[arg(long, value_name = "FILE")]
+    report: Option<PathBuf>,
+
+    #[command(flatten)]
+    service: ServiceArgs,
+}
+
+/// Parse une couleur `#rrggbb[aa]`
+fn parse_color(arg: &str) -> Result<HexColor, String> {
+    HexColor::try_from(arg.to_owned())
+}
+
+/// Parse la valeur de `--pan X,Y`
+fn parse_pan(arg: &str) -> Result<[f32; 2], String> {
+    arg.split_once(',')
+        .and_then(|(x, y)| Some([x.trim().parse().ok()?, y.trim().parse().ok()?]))
+        .ok_or_else(|| format!("expected X,Y, got `{arg}`"))
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse(); // :contentReference[oaicite:2]{index=2}
 
     match cli.command {
-        Commands::Render {
-            json,
-            atlas,
-            out,
-            base_skin,
-            skins,
-        } => {
-            render(&json, &atlas, &out, &base_skin, &skins)?;
+        Commands::Render(args) => {
+            render(&args, None)?;
+        }
+        Commands::Serve(args) => {
+            serve(&args)?;
+        }
+        Commands::Daemon(args) => {
+            let (jobs, receiver) = std::sync::mpsc::channel();
+            std::thread::spawn(move || service::answer_stdin(jobs));
+            run_service(&args, "spine-rs-cli daemon", receiver)?;
+        }
+        Commands::Compare(args) => {
+            compare(&args)?;
+        }
+        Commands::DiffSkeletons(args) => {
+            diff_skeletons(&args)?;
         }
     }
     Ok(())
 }
 
-pub fn render(
-    json_path: &Path,
-    atlas_path: &Path,
-    output_path: &Path,
-    base_skin: &str,
-    skins_to_add: &[String],
-) -> anyhow::Result<()> {
-    // These texture callbacks should be set before loading an atlas.
+/// Convertit un chemin en `&str`, les chemins non UTF-8 étant refusés
+fn path_str<'a>(path: &'a Path, what: &str) -> anyhow::Result<&'a str> {
+    path.to_str()
+        .ok_or_else(|| anyhow::anyhow!("Invalid {what} path"))
+}
+
+/// Convertit un chemin en `&'static str` pour le passer au `Stage`
+fn leak_path(path: &Path, what: &str) -> anyhow::Result<&'static str> {
+    Ok(Box::leak(path_str(path, what)?.to_owned().into_boxed_str()))
+}
+
+fn leak_optional_path(path: Option<&Path>, what: &str) -> anyhow::Result<Option<&'static str>> {
+    path.map(|path| leak_path(path, what)).transpose()
+}
+
+/// Installe les callbacks de création et de libération des textures des pages d'atlas, avant
+/// tout chargement d'atlas. Les textures libérées attendent dans la file renvoyée d'être
+/// supprimées par le `Stage`.
+fn set_texture_callbacks() -> Arc<Mutex<Vec<Texture>>> {
     rusty_spine::extension::set_create_texture_cb(|atlas_page, path| {
-        fn convert_filter(filter: AtlasFilter) -> FilterMode {
-            match filter {
-                AtlasFilter::Linear => FilterMode::Linear,
-                AtlasFilter::Nearest => FilterMode::Nearest,
-                filter => {
-                    println!("Unsupported texture filter mode: {filter:?}");
-                    FilterMode::Linear
-                }
-            }
-        }
         fn convert_wrap(wrap: AtlasWrap) -> TextureWrap {
             match wrap {
                 AtlasWrap::ClampToEdge => TextureWrap::Clamp,
                 AtlasWrap::MirroredRepeat => TextureWrap::Mirror,
                 AtlasWrap::Repeat => TextureWrap::Repeat,
-                wrap => {
-                    println!("Unsupported texture wrap mode: {wrap:?}");
-                    TextureWrap::Clamp
-                }
-            }
-        }
-        fn convert_format(format: AtlasFormat) -> TextureFormat {
-            match format {
-                AtlasFormat::RGB888 => TextureFormat::RGB8,
-                AtlasFormat::RGBA8888 => TextureFormat::RGBA8,
-                format => {
-                    println!("Unsupported texture format: {format:?}");
-                    TextureFormat::RGBA8
-                }
+                // valeur par défaut des atlas Spine
+                AtlasWrap::Unknown => TextureWrap::Clamp,
             }
         }
         atlas_page
             .renderer_object()
             .set(SpineTexture::NeedsToBeLoaded {
                 path: path.to_owned(),
-                min_filter: convert_filter(atlas_page.min_filter()),
-                mag_filter: convert_filter(atlas_page.mag_filter()),
+                min_filter: atlas_page.min_filter(),
+                mag_filter: atlas_page.mag_filter(),
                 x_wrap: convert_wrap(atlas_page.u_wrap()),
                 y_wrap: convert_wrap(atlas_page.v_wrap()),
-                format: convert_format(atlas_page.format()),
+                format: atlas_page.format(),
             });
     });
 
-    // Charger l’atlas Spine
-    let atlas = Arc::new(Atlas::new_from_file(atlas_path)?);
-
-    // Lire le JSON de squelette
-    let skeleton_json = SkeletonJson::new(atlas.clone());
-    let skeleton_data = Arc::new(skeleton_json.read_skeleton_data_file(json_path)?);
-
-    // Composer le skin
-    let mut composite;
-    if base_skin.is_empty() {
-        composite = Skin::new("composite");
-    } else {
-        composite = skeleton_data
-            .find_skin(base_skin)
-            .expect("Base skin not found")
-            .clone();
-    }
-
-    for skin in skins_to_add {
-        let s = skeleton_data
-            .find_skin(skin)
-            .expect(format!("Additional skin not found {:?}", skin).as_str());
-        unsafe { composite.add_skin(&s) };
-    }
-    let composite_static: &'static Skin = Box::leak(Box::new(composite));
-
-    let atlas_path_string = atlas_path
-        .to_str()
-        .ok_or_else(|| anyhow::anyhow!("Invalid atlas path"))?
-        .to_owned()
-        .into_boxed_str();
-    // on fuit la Box<str> pour obtenir &'static str
-    let atlas_path_static: &'static str = Box::leak(atlas_path_string);
-
-    let skeleton_path_string = json_path
-        .to_str()
-        .ok_or_else(|| anyhow::anyhow!("Invalid skeleton path"))?
-        .to_owned()
-        .into_boxed_str();
-    let skeleton_path_static: &'static str = Box::leak(skeleton_path_string);
-
-    let conf = conf::Conf {
-        window_title: "spine-rs-cli".to_owned(),
-        high_dpi: true,
-        ..Default::default()
-    };
-
     let texture_delete_queue: Arc<Mutex<Vec<Texture>>> = Arc::new(Mutex::new(vec![]));
     let texture_delete_queue_cb = texture_delete_queue.clone();
     rusty_spine::extension::set_dispose_texture_cb(move |atlas_page| unsafe {
@@ -174,20 +457,199 @@ pub fn render(
         }
         atlas_page.renderer_object().dispose::<SpineTexture>();
     });
+    texture_delete_queue
+}
 
-    // 2) Passe les références &atlas_path_str et &skeleton_path_str
-    let spine_info = SpineInfo {
-        atlas_path: atlas_path_static,
-        skeleton_path: SpineSkeletonPath::Json(skeleton_path_static),
-        animation: "Idle_Happy",
-        position: Vec2::ZERO,
-        scale: 1.0,
-        skin: Some(&composite_static),
-        backface_culling: false,
+/// Source des fichiers de `--assets` : dossier racine ou archive zip, le disque par défaut
+fn open_assets(path: Option<&Path>) -> anyhow::Result<&'static dyn AssetResolver> {
+    Ok(match path {
+        Some(path) if path.extension().is_some_and(|ext| ext == "zip") => {
+            Box::leak(Box::new(ZipResolver::open(path)?))
+        }
+        Some(path) => Box::leak(Box::new(FileSystem {
+            root: path.to_owned(),
+        })),
+        None => Box::leak(Box::<FileSystem>::default()),
+    })
+}
+
+/// Décrit la scène d'un seul squelette passée par `--json`/`--atlas` et les options associées
+fn cli_scene(args: &RenderArgs, skeleton: PathBuf, atlas: PathBuf) -> Scene {
+    let instance = SkeletonInstance {
+        skins: std::iter::once(args.base_skin.clone())
+            .filter(|skin| !skin.is_empty())
+            .chain(args.skins.iter().cloned())
+            .collect(),
+        animation: Some("Idle_Happy".to_owned()),
+        scale: args.scale.unwrap_or_default(),
+        flip_x: args.flip_x,
+        flip_y: args.flip_y,
+        replace_regions: args.replace_region.iter().cloned().collect(),
+        ..SkeletonInstance::new(skeleton, atlas)
+    };
+    Scene {
+        canvas: Canvas::default(),
+        skeletons: vec![instance],
+    }
+}
+
+/// Rend `args.render` et le compare à la référence, voir [`Comparison`].
+fn compare(args: &CompareArgs) -> anyhow::Result<()> {
+    if args.render.watch {
+        anyhow::bail!("compare renders once, --watch is not supported");
+    }
+    if args.tolerance < 0. {
+        anyhow::bail!("--tolerance must not be negative");
+    }
+    let reference = image::open(&args.reference)
+        .with_context(|| format!("failed to read {}", args.reference.display()))?
+        .to_rgba8();
+    let comparison = Comparison {
+        reference_path: args.reference.clone(),
+        reference,
+        metric: args.metric,
+        tolerance: args.tolerance,
+        max_diff_pixels: args.max_diff_pixels,
+        diff_path: args.diff.clone(),
+    };
+    render(&args.render, Some(Box::leak(Box::new(comparison))))
+}
+
+/// Rend `args`, et le vérifie contre `comparison` pour `compare`.
+pub fn render(args: &RenderArgs, comparison: Option<&'static Comparison>) -> anyhow::Result<()> {
+    let RenderArgs {
+        out: output_path, ..
+    } = args;
+    let started_at = Instant::now();
+
+    if args.slot_ids.is_some() && args.mode != RenderMode::SlotMask {
+        anyhow::bail!("--slot-ids requires --mode slot-mask");
+    }
+    if args.watch && output_path == Path::new("none") {
+        anyhow::bail!("--watch requires --out, the viewer already reloads changed files");
+    }
+    let format = match (args.format, output_path.to_str()) {
+        (Some(format), _) => format,
+        (None, Some("none")) => OutputFormat::Png,
+        (None, _) => OutputFormat::from_path(output_path)?,
+    };
+    if args.quality.is_some() && !matches!(format, OutputFormat::Jpeg | OutputFormat::Webp) {
+        anyhow::bail!("--quality only applies to JPEG and WebP");
+    }
+    let png = matches!(format, OutputFormat::Png | OutputFormat::Png16);
+    if !png && (args.png_compression.is_some() || args.optimize || args.metadata) {
+        anyhow::bail!("--png-compression, --optimize and --metadata only apply to PNG");
+    }
+    if output_path == Path::new("-") {
+        if args.watch {
+            anyhow::bail!("--watch cannot write the image to stdout");
+        }
+        if args.report.as_deref() == Some(Path::new("-")) {
+            anyhow::bail!("--out and --report cannot both write to stdout");
+        }
+    }
+
+    let texture_delete_queue = set_texture_callbacks();
+
+    // Source des fichiers : disque (par défaut), dossier racine ou archive zip
+    let (assets, mut scene): (&'static dyn AssetResolver, _) = match (&args.bundle, &args.scene) {
+        // dans un bundle, squelette et atlas sont cherchés quand ils ne sont pas donnés
+        (Some(bundle), _) => {
+            let zip = ZipResolver::open(bundle)?;
+            let json_path = match &args.json {
+                Some(path) => path.clone(),
+                None => zip.locate(&[".json", ".skel"], None, "skeleton")?.into(),
+            };
+            let atlas_path = match &args.atlas {
+                Some(path) => path.clone(),
+                None => {
+                    let stem = json_path.file_stem().and_then(|stem| stem.to_str());
+                    zip.locate(&[".atlas", ".atlas.txt"], stem, "atlas")?.into()
+                }
+            };
+            (
+                Box::leak(Box::new(zip)),
+                cli_scene(args, json_path, atlas_path),
+            )
+        }
+        (None, scene_path) => {
+            let mut assets = open_assets(args.assets.as_deref())?;
+            // `--json -` : le squelette est lu une fois pour toutes sur stdin
+            if scene_path.is_none() && args.json.as_deref() == Some(Path::new("-")) {
+                let mut stdin = vec![];
+                std::io::stdin()
+                    .read_to_end(&mut stdin)
+                    .context("failed to read the skeleton from stdin")?;
+                assets = Box::leak(Box::new(WithStdin {
+                    stdin,
+                    inner: assets,
+                }));
+            }
+            let scene = match scene_path {
+                Some(scene_path) => {
+                    let mut scene = Scene::load(scene_path)?;
+                    // sans --assets, les chemins de la scène sont relatifs à son dossier
+                    if args.assets.is_none() {
+                        scene.relative_to(scene_path.parent().unwrap_or(Path::new("")));
+                    }
+                    scene
+                }
+                // présents grâce à `required_unless_present_any`
+                None => cli_scene(
+                    args,
+                    args.json.clone().unwrap(),
+                    args.atlas.clone().unwrap(),
+                ),
+            };
+            (assets, scene)
+        }
+    };
+    let camera = &mut scene.canvas.camera;
+    if let Some([x, y]) = args.pan {
+        (camera.x, camera.y) = (x, y);
+    }
+    camera.zoom = args.zoom.unwrap_or(camera.zoom);
+    camera.rotation = args.rotation.unwrap_or(camera.rotation);
+    scene.validate()?;
+    // les squelettes de z le plus élevé sont dessinés en dernier
+    scene.skeletons.sort_by_key(|instance| instance.z);
+
+    let load_options = LoadOptions {
+        debug: Box::leak(args.debug.clone().into_boxed_slice()),
+        atlas_alpha: args.color.atlas_alpha,
+        color_space: args.color.color_space,
+    };
+    // la scène est vérifiée avant d'ouvrir la fenêtre, `Stage::new` relit ensuite les fichiers
+    let spine_infos = scene
+        .skeletons
+        .iter()
+        .map(|instance| {
+            let info = SpineInfo::load(assets, instance, &load_options)?;
+            let (atlas, skeleton_data) =
+                read_skeleton_data(assets, info.skeleton_path.path(), &info.atlas_path)?;
+            info.check(&atlas, &skeleton_data)?;
+            Ok(info)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let canvas = scene.canvas;
+    let open_image = |path: &Option<PathBuf>, fit| {
+        path.as_deref()
+            .map(|path| anyhow::Ok(&*Box::leak(Box::new(CanvasImage::open(path, fit)?))))
+            .transpose()
+    };
+    let layers = CanvasLayers {
+        background: open_image(&args.background_image, args.background_fit)?,
+        overlay: open_image(&args.overlay_image, args.overlay_fit)?,
+    };
+    let conf = conf::Conf {
+        window_title: "spine-rs-cli".to_owned(),
+        window_width: canvas.width as i32,
+        window_height: canvas.height as i32,
+        high_dpi: true,
+        ..Default::default()
     };
-    let spine_info_static: &'static SpineInfo = Box::leak(Box::new(spine_info));
 
-    let render_png: &'static bool = Box::leak(Box::new(output_path.to_str().unwrap() != "none"));
     let output_path_static: &'static str = Box::leak(
         output_path
             .to_str()
@@ -195,16 +657,202 @@ pub fn render(
             .to_owned()
             .into_boxed_str(),
     );
+    let output_info = OutputInfo {
+        png_path: output_path_static,
+        report_path: leak_optional_path(args.report.as_deref(), "report")?,
+        hitmap_path: leak_optional_path(args.hitmap.as_deref(), "hit map")?,
+        hitmap_mask_path: leak_optional_path(args.hitmap_mask.as_deref(), "hit mask")?,
+        mode: args.mode,
+        slot_ids_path: leak_optional_path(args.slot_ids.as_deref(), "slot IDs")?,
+        slot_legend_path: leak_optional_path(args.slot_legend.as_deref(), "slot legend")?,
+        alpha: args.color.output_alpha,
+        encoding: Encoding {
+            format,
+            quality: args.quality,
+            matte: args.matte.0,
+            png_compression: args.png_compression.unwrap_or(match args.optimize {
+                true => PngCompression::Best,
+                false => PngCompression::Default,
+            }),
+            optimize: args.optimize,
+            metadata: args.metadata,
+        },
+        color_space: args.color.color_space,
+        effects: Effects {
+            outline: args.outline,
+            drop_shadow: args.drop_shadow,
+            glow: args.glow,
+        },
+        comparison,
+        watch: args.watch,
+        started_at,
+    };
 
-    miniquad::start(conf, |ctx| {
+    miniquad::start(conf, move |ctx| {
         Box::new(Stage::new(
             ctx,
             texture_delete_queue,
-            spine_info_static,
-            render_png,
-            output_path_static,
+            &canvas,
+            &layers,
+            &spine_infos,
+            &output_info,
         ))
     });
 
     Ok(())
 }
+
+pub fn serve(args: &ServeArgs) -> anyhow::Result<()> {
+    let server = tiny_http::Server::http(&args.bind)
+        .map_err(|err| anyhow::anyhow!("failed to listen on {}: {err}", args.bind))?;
+    let (jobs, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || service::listen(server, jobs));
+    eprintln!("Listening on http://{}/render", args.bind);
+    run_service(&args.service, "spine-rs-cli serve", receiver)
+}
+
+/// Compare la structure des deux versions de `args`, puis les rend avec le service de rendu
+/// quand une image est demandée
+fn diff_skeletons(args: &DiffSkeletonsArgs) -> anyhow::Result<()> {
+    let assets = open_assets(Some(&args.service.assets))?;
+    let canvas = Canvas {
+        width: args.width,
+        height: args.height,
+        background: args.background,
+        camera: Camera {
+            zoom: args.zoom,
+            ..Default::default()
+        },
+    };
+    let scene = |skeleton: &PathBuf, atlas: &Option<PathBuf>| Scene {
+        canvas,
+        skeletons: vec![SkeletonInstance {
+            skins: args.skins.clone(),
+            animation: args.animation.clone(),
+            time: args.time,
+            ..SkeletonInstance::new(
+                skeleton.clone(),
+                atlas
+                    .clone()
+                    .unwrap_or_else(|| skeleton.with_extension("atlas")),
+            )
+        }],
+    };
+    let old = scene(&args.old_json, &args.old_atlas);
+    let new = scene(&args.new_json, &args.new_atlas);
+
+    let [old_outline, new_outline] = [&old, &new].map(|scene| {
+        let instance = &scene.skeletons[0];
+        let (_, data) = read_skeleton_data(
+            assets,
+            path_str(&instance.skeleton, "skeleton")?,
+            path_str(&instance.atlas, "atlas")?,
+        )?;
+        anyhow::Ok(SkeletonOutline::new(&data))
+    });
+    let diff = StructuralDiff::between(&old_outline?, &new_outline?);
+    match leak_optional_path(args.report.as_deref(), "report")? {
+        Some(report_path) => diff.write_json(report_path)?,
+        None => print!("{diff}"),
+    }
+
+    let Some(out) = args.out.clone() else {
+        return Ok(());
+    };
+    old.validate()?;
+    new.validate()?;
+    let layout = args.layout;
+    let (jobs, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let render = |scene: Scene| {
+            let (reply, result) = std::sync::mpsc::channel();
+            jobs.send(service::Job { scene, reply })
+                .map_err(|_| anyhow::anyhow!("the renderer has stopped"))?;
+            let (image, _) = result
+                .recv()
+                .map_err(|_| anyhow::anyhow!("the renderer has stopped"))??;
+            anyhow::Ok(image)
+        };
+        let written = render(old).and_then(|old| {
+            let (image, differing) = skeleton_diff::compose(layout, &old, &render(new)?);
+            image
+                .save(&out)
+                .with_context(|| format!("failed to write {}", out.display()))?;
+            anyhow::Ok(differing)
+        });
+        match written {
+            // le service s'arrête avec la fermeture de `jobs`
+            Ok(differing) => eprintln!(
+                "✅ {} written, {differing} pixels differ between the versions",
+                out.display()
+            ),
+            Err(err) => {
+                eprintln!("Error: {err:#}");
+                std::process::exit(1);
+            }
+        }
+    });
+    run_service(&args.service, "spine-rs-cli diff-skeletons", receiver)
+}
+
+/// Rend les travaux reçus sur `jobs` jusqu'à la fermeture du canal
+fn run_service(
+    args: &ServiceArgs,
+    title: &str,
+    jobs: std::sync::mpsc::Receiver<service::Job>,
+) -> anyhow::Result<()> {
+    let assets = open_assets(Some(&args.assets))?;
+    let texture_delete_queue = set_texture_callbacks();
+
+    let load_options = LoadOptions {
+        debug: &[],
+        atlas_alpha: args.color.atlas_alpha,
+        color_space: args.color.color_space,
+    };
+    let output_info = OutputInfo {
+        // les images sont renvoyées au client
+        png_path: "-",
+        report_path: None,
+        hitmap_path: None,
+        hitmap_mask_path: None,
+        mode: RenderMode::Color,
+        slot_ids_path: None,
+        slot_legend_path: None,
+        alpha: args.color.output_alpha,
+        encoding: Encoding {
+            format: OutputFormat::Png,
+            quality: None,
+            matte: Color::new_rgba(1., 1., 1., 1.),
+            png_compression: PngCompression::Default,
+            optimize: false,
+            metadata: false,
+        },
+        color_space: args.color.color_space,
+        effects: Effects::default(),
+        comparison: None,
+        watch: false,
+        started_at: Instant::now(),
+    };
+    let canvas = Canvas::default();
+    // miniquad a besoin d'une fenêtre pour son contexte GL, les rendus restent hors écran
+    let conf = conf::Conf {
+        window_title: title.to_owned(),
+        window_width: 320,
+        window_height: 240,
+        ..Default::default()
+    };
+
+    miniquad::start(conf, move |ctx| {
+        let stage = Stage::new(
+            ctx,
+            texture_delete_queue,
+            &canvas,
+            &CanvasLayers::default(),
+            &[],
+            &output_info,
+        );
+        Box::new(service::Service::new(stage, jobs, assets, load_options))
+    });
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use clap::ValueEnum;
+use image::{ImageBuffer, Luma, RgbaImage};
+use rusty_spine::Skeleton;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::palette::id_color;
+
+/// What the render pass writes into the output image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum RenderMode {
+    /// Textured, tinted skeleton
+    #[default]
+    Color,
+    /// One flat color per slot, cut out by the texture alpha
+    SlotMask,
+}
+
+/// Flat color and numeric ID assigned to a slot in the mask pass.
+#[derive(Debug, Serialize)]
+pub struct SlotLegendEntry {
+    /// Value written in the `--slot-ids` image, 0 being the background.
+    pub id: u16,
+    /// Index of the skeleton in the scene, in drawing order.
+    pub skeleton: usize,
+    pub slot: String,
+    pub color: [u8; 3],
+}
+
+/// Mapping between mask colors, IDs and slot names, written as JSON for compositing tools.
+#[derive(Debug, Default, Serialize)]
+pub struct SlotLegend {
+    pub slots: Vec<SlotLegendEntry>,
+    /// Index in `slots` of the first slot of each skeleton.
+    #[serde(skip)]
+    offsets: Vec<usize>,
+}
+
+impl SlotLegend {
+    /// Assigns an ID and a unique color to every slot of the skeletons, in setup order so that
+    /// they stay stable across poses and draw order changes.
+    pub fn new<'a>(skeletons: impl IntoIterator<Item = &'a Skeleton>) -> Self {
+        let mut slots = vec![];
+        let mut offsets = vec![];
+        for (skeleton_index, skeleton) in skeletons.into_iter().enumerate() {
+            offsets.push(slots.len());
+            for slot in skeleton.slots() {
+                slots.push(SlotLegendEntry {
+                    id: slots.len() as u16 + 1,
+                    skeleton: skeleton_index,
+                    slot: slot.data().name().to_owned(),
+                    color: id_color(slots.len()),
+                });
+            }
+        }
+        Self { slots, offsets }
+    }
+
+    /// Mask color of the slot at `slot_index` in [`Skeleton::slots`] of the skeleton at
+    /// `skeleton_index`.
+    pub fn color(&self, skeleton_index: usize, slot_index: usize) -> [u8; 3] {
+        self.slots[self.offsets[skeleton_index] + slot_index].color
+    }
+
+    pub fn write_json(&self, path: &str) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(())
+    }
+
+    /// Decodes a mask image back into a 16-bit image of slot IDs.
+    pub fn write_ids(&self, mask: &RgbaImage, path: &str) -> anyhow::Result<()> {
+        let ids: HashMap<[u8; 3], u16> = self
+            .slots
+            .iter()
+            .map(|entry| (entry.color, entry.id))
+            .collect();
+        let id_image: ImageBuffer<Luma<u16>, Vec<u16>> =
+            ImageBuffer::from_fn(mask.width(), mask.height(), |x, y| {
+                let [r, g, b, a] = mask.get_pixel(x, y).0;
+                Luma([if a == 0 {
+                    0
+                } else {
+                    ids.get(&[r, g, b]).copied().unwrap_or(0)
+                }])
+            });
+        id_image.save(path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// What an image was rendered from, embedded in PNG outputs with `--metadata` so that assets
+/// can be traced back to the exact skeleton export.
+#[derive(Debug, Serialize)]
+pub struct Provenance {
+    pub tool: &'static str,
+    pub version: &'static str,
+    /// In drawing order.
+    pub skeletons: Vec<SkeletonProvenance>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SkeletonProvenance {
+    pub skeleton: String,
+    /// SHA-256 of the skeleton file, in hex.
+    pub sha256: String,
+    pub atlas: String,
+    pub skins: Vec<String>,
+    /// Animation of each track, empty for the setup pose.
+    pub tracks: Vec<TrackProvenance>,
+    /// Seconds into the animations.
+    pub time: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrackProvenance {
+    pub track: usize,
+    pub animation: String,
+}
+
+impl Provenance {
+    pub fn new(skeletons: Vec<SkeletonProvenance>) -> Self {
+        Self {
+            tool: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            skeletons,
+        }
+    }
+
+    /// PNG text chunks: `Software` for image viewers, and the whole record as JSON under
+    /// `Provenance`.
+    pub fn text_chunks(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Software", format!("{} {}", self.tool, self.version)),
+            (
+                "Provenance",
+                serde_json::to_string(self).expect("serializable provenance"),
+            ),
+        ]
+    }
+}
+
+/// SHA-256 digest of `bytes`, in lowercase hex.
+pub fn sha256(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
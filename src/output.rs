@@ -0,0 +1,513 @@
+use anyhow::Context;
+use clap::ValueEnum;
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageBuffer, ImageFormat, Rgb, RgbImage, Rgba, Rgba32FImage, RgbaImage};
+use rusty_spine::draw::ColorSpace;
+use rusty_spine::Color;
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Write};
+use std::path::Path;
+
+/// How color channels relate to alpha, for atlases and written images.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum AlphaMode {
+    /// Color channels independent of alpha, what PNG viewers expect
+    #[default]
+    Straight,
+    /// Color channels already multiplied by alpha
+    Premultiplied,
+}
+
+/// Space in which textures and vertex colors are blended.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorSpaceMode {
+    /// Blend the sRGB values as is, like the Spine editor
+    #[default]
+    Srgb,
+    /// Linearize textures and colors, blend in linear light and encode to sRGB on output. The
+    /// render target keeps 8 bits per channel, so dark gradients show banding
+    Linear,
+}
+
+/// Encoding of the written image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// 8-bit PNG
+    Png,
+    /// 16-bit PNG of the 8-bit render target, unpremultiplied and encoded without rounding to
+    /// 8 bits again
+    Png16,
+    /// JPEG, flattened on the matte color since it has no alpha
+    Jpeg,
+    /// WebP, lossless unless a quality is given
+    Webp,
+    /// 8-bit TIFF
+    Tiff,
+    /// 32-bit float OpenEXR, in linear light with premultiplied alpha
+    Exr,
+}
+
+impl OutputFormat {
+    /// Format matching the extension of `path`, PNG for stdout.
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        if path == Path::new("-") {
+            return Ok(OutputFormat::Png);
+        }
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        Ok(match extension.as_deref() {
+            Some("png") => OutputFormat::Png,
+            Some("jpg" | "jpeg") => OutputFormat::Jpeg,
+            Some("webp") => OutputFormat::Webp,
+            Some("tif" | "tiff") => OutputFormat::Tiff,
+            Some("exr") => OutputFormat::Exr,
+            _ => anyhow::bail!(
+                "cannot tell the format of {} from its extension, use --format",
+                path.display()
+            ),
+        })
+    }
+}
+
+/// zlib effort of PNG outputs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum PngCompression {
+    /// Quickest to write, largest files
+    Fast,
+    #[default]
+    Default,
+    /// Slowest to write, smallest files
+    Best,
+}
+
+/// How the written image is encoded, besides its [`AlphaMode`].
+#[derive(Clone, Copy, Debug)]
+pub struct Encoding {
+    pub format: OutputFormat,
+    /// JPEG or lossy WebP quality, from 1 to 100. Without it, JPEG uses 90 and WebP is lossless.
+    pub quality: Option<u8>,
+    /// Background the image is flattened on, for formats without alpha.
+    pub matte: Color,
+    pub png_compression: PngCompression,
+    /// Writes PNGs with the fewest channels and bits that hold every pixel exactly.
+    pub optimize: bool,
+    /// Embeds the [`Provenance`](crate::metadata::Provenance) of the render in PNGs.
+    pub metadata: bool,
+}
+
+/// Pixels read back from the render target: premultiplied, and linear light when `linear` is
+/// set. Converted to the written image at the precision of its format.
+pub struct Readback {
+    pub pixels: RgbaImage,
+    pub linear: bool,
+}
+
+impl Readback {
+    /// 8-bit image in `alpha_mode`, see [`convert_readback`].
+    pub fn to_rgba8(&self, alpha_mode: AlphaMode) -> RgbaImage {
+        let mut image = self.pixels.clone();
+        convert_readback(&mut image, alpha_mode, self.linear);
+        image
+    }
+
+    /// 16-bit image in `alpha_mode`, widened from the 8-bit pixels. Only unpremultiplying and
+    /// sRGB encoding are done in floating point, so that they add no rounding of their own; the
+    /// render itself has no more than 8 bits of precision.
+    pub fn to_rgba16(&self, alpha_mode: AlphaMode) -> ImageBuffer<Rgba<u16>, Vec<u16>> {
+        ImageBuffer::from_fn(self.pixels.width(), self.pixels.height(), |x, y| {
+            let [r, g, b, a] = self.straight(*self.pixels.get_pixel(x, y));
+            let premultiply = match alpha_mode {
+                AlphaMode::Straight => 1.,
+                AlphaMode::Premultiplied => a,
+            };
+            let quantize = |channel: f32| (channel * 65535.).round() as u16;
+            Rgba([
+                quantize(r * premultiply),
+                quantize(g * premultiply),
+                quantize(b * premultiply),
+                quantize(a),
+            ])
+        })
+    }
+
+    /// Linear light with premultiplied alpha, as compositing applications expect from EXR.
+    pub fn to_linear(&self) -> Rgba32FImage {
+        ImageBuffer::from_fn(self.pixels.width(), self.pixels.height(), |x, y| {
+            let [r, g, b, a] = self.straight(*self.pixels.get_pixel(x, y));
+            Rgba([
+                srgb_to_linear(r) * a,
+                srgb_to_linear(g) * a,
+                srgb_to_linear(b) * a,
+                a,
+            ])
+        })
+    }
+
+    /// Straight, sRGB encoded color of `pixel` and its alpha, from 0 to 1.
+    fn straight(&self, pixel: Rgba<u8>) -> [f32; 4] {
+        let [r, g, b, a] = pixel.0.map(|channel| channel as f32 / 255.);
+        if a == 0. {
+            return [0.; 4];
+        }
+        let encode = |channel: f32| {
+            let straight = (channel / a).min(1.);
+            match self.linear {
+                true => linear_to_srgb(straight),
+                false => straight,
+            }
+        };
+        [encode(r), encode(g), encode(b), a]
+    }
+}
+
+impl From<ColorSpaceMode> for ColorSpace {
+    fn from(mode: ColorSpaceMode) -> Self {
+        match mode {
+            ColorSpaceMode::Srgb => ColorSpace::SRGB,
+            ColorSpaceMode::Linear => ColorSpace::Linear,
+        }
+    }
+}
+
+/// Converts pixels read back from the render target, which always hold premultiplied color
+/// since every blend mode composites over a transparent black clear, into `alpha_mode`.
+///
+/// When `linear` is set the render target holds linear light, which is encoded to sRGB.
+pub fn convert_readback(image: &mut RgbaImage, alpha_mode: AlphaMode, linear: bool) {
+    if alpha_mode == AlphaMode::Premultiplied && !linear {
+        return;
+    }
+    for pixel in image.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+        if a == 0 {
+            pixel.0 = [0, 0, 0, 0];
+            continue;
+        }
+        if !linear {
+            if a < 255 {
+                let unpremultiply =
+                    |channel: u8| ((channel as u32 * 255 + a as u32 / 2) / a as u32).min(255) as u8;
+                pixel.0 = [unpremultiply(r), unpremultiply(g), unpremultiply(b), a];
+            }
+            continue;
+        }
+        let alpha = a as f32 / 255.;
+        let premultiply = match alpha_mode {
+            AlphaMode::Straight => 1.,
+            AlphaMode::Premultiplied => alpha,
+        };
+        let encode = |channel: u8| {
+            let straight = (channel as f32 / 255. / alpha).min(1.);
+            (linear_to_srgb(straight) * premultiply * 255.).round() as u8
+        };
+        pixel.0 = [encode(r), encode(g), encode(b), a];
+    }
+}
+
+/// Encodes `frame` and writes it to `path`, or to stdout when `path` is `-`. `alpha_mode`
+/// does not apply to JPEG, which has no alpha, nor to EXR, always premultiplied. `text` is
+/// embedded in PNGs as `(keyword, text)` chunks.
+pub fn write_image(
+    frame: &Readback,
+    path: &str,
+    alpha_mode: AlphaMode,
+    encoding: &Encoding,
+    text: &[(&str, String)],
+) -> anyhow::Result<()> {
+    let mut bytes = Cursor::new(vec![]);
+    match encoding.format {
+        OutputFormat::Png => {
+            let image = frame.to_rgba8(alpha_mode);
+            let pixels = PngPixels::from_rgba8(&image, encoding.optimize);
+            pixels.write(bytes.get_mut(), encoding, text)?;
+        }
+        OutputFormat::Png16 => {
+            let image = frame.to_rgba16(alpha_mode);
+            let pixels = PngPixels::from_rgba16(&image, encoding.optimize);
+            pixels.write(bytes.get_mut(), encoding, text)?;
+        }
+        OutputFormat::Jpeg => {
+            let image = flatten(&frame.to_rgba8(AlphaMode::Straight), encoding.matte);
+            JpegEncoder::new_with_quality(&mut bytes, encoding.quality.unwrap_or(90))
+                .encode_image(&image)?;
+        }
+        OutputFormat::Webp => {
+            let image = frame.to_rgba8(alpha_mode);
+            let encoder = webp::Encoder::from_rgba(&image, image.width(), image.height());
+            let webp = match encoding.quality {
+                Some(quality) => encoder.encode(quality as f32),
+                None => encoder.encode_lossless(),
+            };
+            bytes.get_mut().extend_from_slice(&webp);
+        }
+        OutputFormat::Tiff => frame
+            .to_rgba8(alpha_mode)
+            .write_to(&mut bytes, ImageFormat::Tiff)?,
+        OutputFormat::Exr => DynamicImage::ImageRgba32F(frame.to_linear())
+            .write_to(&mut bytes, ImageFormat::OpenExr)?,
+    }
+    if path == "-" {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(bytes.get_ref())?;
+        stdout.flush()?;
+    } else {
+        std::fs::write(path, bytes.get_ref()).with_context(|| format!("failed to write {path}"))?;
+    }
+    Ok(())
+}
+
+/// Raw PNG image data, reduced by `--optimize`.
+struct PngPixels {
+    width: u32,
+    height: u32,
+    color: png::ColorType,
+    depth: png::BitDepth,
+    /// Rows of packed samples, 16-bit ones big endian.
+    data: Vec<u8>,
+    /// RGB entries and their alphas, the opaque ones left out at the end, for indexed data.
+    palette: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl PngPixels {
+    /// With `optimize`, a palette when there are at most 256 colors, packed in as few bits as
+    /// possible, or RGB when every pixel is opaque.
+    fn from_rgba8(image: &RgbaImage, optimize: bool) -> Self {
+        let (width, height) = image.dimensions();
+        if optimize {
+            if let Some(indexed) = Self::indexed(image) {
+                return indexed;
+            }
+            if image.pixels().all(|pixel| pixel.0[3] == 255) {
+                return Self {
+                    width,
+                    height,
+                    color: png::ColorType::Rgb,
+                    depth: png::BitDepth::Eight,
+                    data: image
+                        .pixels()
+                        .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+                        .collect(),
+                    palette: None,
+                };
+            }
+        }
+        Self {
+            width,
+            height,
+            color: png::ColorType::Rgba,
+            depth: png::BitDepth::Eight,
+            data: image.as_raw().clone(),
+            palette: None,
+        }
+    }
+
+    /// With `optimize`, RGB when every pixel is opaque.
+    fn from_rgba16(image: &ImageBuffer<Rgba<u16>, Vec<u16>>, optimize: bool) -> Self {
+        let opaque = optimize && image.pixels().all(|pixel| pixel.0[3] == u16::MAX);
+        let channels = if opaque { 3 } else { 4 };
+        Self {
+            width: image.width(),
+            height: image.height(),
+            color: match opaque {
+                true => png::ColorType::Rgb,
+                false => png::ColorType::Rgba,
+            },
+            depth: png::BitDepth::Sixteen,
+            data: image
+                .pixels()
+                .flat_map(|pixel| pixel.0.into_iter().take(channels))
+                .flat_map(u16::to_be_bytes)
+                .collect(),
+            palette: None,
+        }
+    }
+
+    /// The image as palette indices, `None` when it has more than 256 colors.
+    fn indexed(image: &RgbaImage) -> Option<Self> {
+        let mut colors: Vec<[u8; 4]> = vec![];
+        let mut seen = HashSet::new();
+        for pixel in image.pixels() {
+            if seen.insert(pixel.0) {
+                if colors.len() == 256 {
+                    return None;
+                }
+                colors.push(pixel.0);
+            }
+        }
+        // translucent entries first, so that the tRNS chunk stops at the last of them
+        colors.sort_by_key(|color| color[3] == 255);
+        let indices: HashMap<[u8; 4], usize> = colors
+            .iter()
+            .enumerate()
+            .map(|(index, &color)| (color, index))
+            .collect();
+
+        let (bits, depth) = match colors.len() {
+            0..=2 => (1, png::BitDepth::One),
+            3..=4 => (2, png::BitDepth::Two),
+            5..=16 => (4, png::BitDepth::Four),
+            _ => (8, png::BitDepth::Eight),
+        };
+        let (width, height) = image.dimensions();
+        let row_bytes = (width as usize * bits).div_ceil(8);
+        let mut data = vec![0; row_bytes * height as usize];
+        for (y, row) in image.rows().enumerate() {
+            for (x, pixel) in row.enumerate() {
+                let bit = x * bits;
+                data[y * row_bytes + bit / 8] |= (indices[&pixel.0] << (8 - bits - bit % 8)) as u8;
+            }
+        }
+        Some(Self {
+            width,
+            height,
+            color: png::ColorType::Indexed,
+            depth,
+            data,
+            palette: Some((
+                colors
+                    .iter()
+                    .flat_map(|color| [color[0], color[1], color[2]])
+                    .collect(),
+                colors
+                    .iter()
+                    .take_while(|color| color[3] != 255)
+                    .map(|color| color[3])
+                    .collect(),
+            )),
+        })
+    }
+
+    fn write(
+        &self,
+        bytes: &mut Vec<u8>,
+        encoding: &Encoding,
+        text: &[(&str, String)],
+    ) -> anyhow::Result<()> {
+        let mut encoder = png::Encoder::new(bytes, self.width, self.height);
+        encoder.set_color(self.color);
+        encoder.set_depth(self.depth);
+        if let Some((palette, alphas)) = &self.palette {
+            encoder.set_palette(palette.as_slice());
+            if !alphas.is_empty() {
+                encoder.set_trns(alphas.as_slice());
+            }
+        }
+        encoder.set_compression(match encoding.png_compression {
+            PngCompression::Fast => png::Compression::Fast,
+            PngCompression::Default => png::Compression::Default,
+            PngCompression::Best => png::Compression::Best,
+        });
+        if encoding.optimize {
+            // filters rarely help palette indices
+            match self.palette {
+                Some(_) => encoder.set_filter(png::FilterType::NoFilter),
+                None => encoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive),
+            }
+        }
+        for (keyword, text) in text {
+            encoder.add_itxt_chunk(keyword.to_string(), text.clone())?;
+        }
+        encoder.write_header()?.write_image_data(&self.data)?;
+        Ok(())
+    }
+}
+
+/// Composites a straight alpha `image` over an opaque `matte`.
+fn flatten(image: &RgbaImage, matte: Color) -> RgbImage {
+    let matte = [matte.r, matte.g, matte.b];
+    ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = image.get_pixel(x, y).0;
+        let alpha = a as f32 / 255.;
+        let blend = |channel: u8, matte: f32| {
+            (channel as f32 * alpha + matte * 255. * (1. - alpha)).round() as u8
+        };
+        Rgb([blend(r, matte[0]), blend(g, matte[1]), blend(b, matte[2])])
+    })
+}
+
+pub fn srgb_to_linear(x: f32) -> f32 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(x: f32) -> f32 {
+    if x <= 0.0031308 {
+        x * 12.92
+    } else {
+        1.055 * x.powf(1. / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `image` as indexed PNG and decodes it back to RGBA, along with the bit depth of
+    /// its indices.
+    fn indexed_round_trip(image: &RgbaImage) -> (RgbaImage, png::BitDepth) {
+        let encoding = Encoding {
+            format: OutputFormat::Png,
+            quality: None,
+            matte: Color::new_rgba(1., 1., 1., 1.),
+            png_compression: PngCompression::Default,
+            optimize: true,
+            metadata: false,
+        };
+        let mut bytes = vec![];
+        PngPixels::indexed(image)
+            .expect("at most 256 colors")
+            .write(&mut bytes, &encoding, &[])
+            .unwrap();
+
+        let mut decoder = png::Decoder::new(bytes.as_slice());
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::ALPHA);
+        let mut reader = decoder.read_info().unwrap();
+        assert_eq!(reader.info().color_type, png::ColorType::Indexed);
+        let depth = reader.info().bit_depth;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut data).unwrap();
+        data.truncate(frame.buffer_size());
+        (
+            RgbaImage::from_raw(frame.width, frame.height, data).unwrap(),
+            depth,
+        )
+    }
+
+    #[test]
+    fn indexed_png_round_trip() {
+        for (colors, depth) in [
+            (2, png::BitDepth::One),
+            (3, png::BitDepth::Two),
+            (4, png::BitDepth::Two),
+            (16, png::BitDepth::Four),
+            (256, png::BitDepth::Eight),
+        ] {
+            // an odd width, so that rows of packed indices end inside a byte
+            let image = RgbaImage::from_fn(13, 20, |x, y| {
+                let index = (y * 13 + x) as usize % colors;
+                // translucent colors come after opaque ones, the palette has to move them first
+                let alpha = match index % 3 {
+                    1 => (index * 7 % 256) as u8,
+                    _ => 255,
+                };
+                Rgba([index as u8, (index * 3) as u8, !(index as u8), alpha])
+            });
+            assert_eq!(
+                indexed_round_trip(&image),
+                (image, depth),
+                "{colors} colors"
+            );
+        }
+    }
+
+    #[test]
+    fn too_many_colors_for_a_palette() {
+        let image = RgbaImage::from_fn(257, 1, |x, _| Rgba([x as u8, (x >> 8) as u8, 0, 255]));
+        assert!(PngPixels::indexed(&image).is_none());
+    }
+}
@@ -0,0 +1,8 @@
+/// A distinct color for the `index`-th item, different for each of the first 2²⁴ - 1 indices.
+/// The index is multiplied by an odd constant modulo 2²⁴, a bijection, so that neighbouring
+/// indices get very different colors.
+pub fn id_color(index: usize) -> [u8; 3] {
+    // la couleur de l'index -1 serait le noir
+    let rgb = (index as u32 + 1).wrapping_mul(0x9e_37_79) & 0xff_ff_ff;
+    [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8]
+}
@@ -0,0 +1,70 @@
+use std::time::SystemTime;
+
+use crate::assets::AssetResolver;
+
+/// Seconds between two checks of the watched files.
+const POLL_INTERVAL: f64 = 0.5;
+
+/// Watches the files of the viewer's skeletons by polling their modification time.
+///
+/// Exporters write the skeleton, atlas and pages one after the other, so a change is only
+/// reported once the files stopped changing for a whole poll interval.
+pub struct AssetWatcher {
+    files: Vec<WatchedFile>,
+    last_poll: f64,
+    /// Files changed since the last reported change, not settled yet.
+    changed: Vec<String>,
+    /// Whether a change was seen on the previous poll.
+    settling: bool,
+}
+
+struct WatchedFile {
+    assets: &'static dyn AssetResolver,
+    path: String,
+    modified: Option<SystemTime>,
+}
+
+impl AssetWatcher {
+    pub fn new(
+        files: impl IntoIterator<Item = (&'static dyn AssetResolver, String)>,
+        now: f64,
+    ) -> Self {
+        let files = files
+            .into_iter()
+            .map(|(assets, path)| WatchedFile {
+                modified: assets.modified(&path),
+                assets,
+                path,
+            })
+            .collect();
+        Self {
+            files,
+            last_poll: now,
+            changed: vec![],
+            settling: false,
+        }
+    }
+
+    /// The files that changed, once they settled, since the last reported change. Checks them
+    /// at most every [`POLL_INTERVAL`].
+    pub fn poll(&mut self, now: f64) -> Option<Vec<String>> {
+        if now - self.last_poll < POLL_INTERVAL {
+            return None;
+        }
+        self.last_poll = now;
+        let mut changed = false;
+        for file in &mut self.files {
+            let modified = file.assets.modified(&file.path);
+            if modified != file.modified {
+                file.modified = modified;
+                changed = true;
+                if !self.changed.contains(&file.path) {
+                    self.changed.push(file.path.clone());
+                }
+            }
+        }
+        let settled = self.settling && !changed;
+        self.settling = changed;
+        settled.then(|| std::mem::take(&mut self.changed))
+    }
+}
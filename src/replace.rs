@@ -0,0 +1,97 @@
+use image::RgbaImage;
+use miniquad::Texture;
+use rusty_spine::{atlas::AtlasRegion, c::spAtlasPage};
+use std::path::PathBuf;
+use std::ptr::null_mut;
+use std::sync::{Arc, Mutex};
+
+use crate::spine::SpineTexture;
+
+/// An atlas region drawn with an external image instead of its atlas page.
+#[derive(Clone, Debug)]
+pub struct RegionReplacement {
+    pub region: String,
+    pub image: RgbaImage,
+}
+
+/// Atlas page made by [`replace_region`], unknown to its atlas. Frees the page when dropped and
+/// queues its texture for deletion, so it must outlive the skeletons drawing the region.
+pub struct ReplacementPage {
+    page: *mut spAtlasPage,
+    texture: *mut SpineTexture,
+    texture_delete_queue: Arc<Mutex<Vec<Texture>>>,
+}
+
+impl Drop for ReplacementPage {
+    fn drop(&mut self) {
+        unsafe {
+            if let SpineTexture::Loaded(texture) = *Box::from_raw(self.texture) {
+                self.texture_delete_queue.lock().unwrap().push(texture);
+            }
+            drop(Box::from_raw(self.page));
+        }
+    }
+}
+
+/// Parses a `--replace-region NAME=FILE` value.
+pub fn parse_replacement(arg: &str) -> Result<(String, PathBuf), String> {
+    match arg.split_once('=') {
+        Some((region, path)) if !region.is_empty() && !path.is_empty() => {
+            Ok((region.to_owned(), path.into()))
+        }
+        _ => Err(format!("expected REGION=FILE, got `{arg}`")),
+    }
+}
+
+/// Moves `region` onto a page of its own holding `replacement.image`, stretched over the
+/// region's original (untrimmed) size.
+///
+/// Must run before the skeleton data is read, since attachments compute their UVs and offsets
+/// from the region when they are created. Every attachment using the region, meshes included,
+/// then draws the replacement.
+pub fn replace_region(
+    region: &AtlasRegion,
+    replacement: &RegionReplacement,
+    premultiplied_alpha: bool,
+    texture_delete_queue: &Arc<Mutex<Vec<Texture>>>,
+) -> ReplacementPage {
+    let mut pixels = replacement.image.clone();
+    if premultiplied_alpha {
+        for pixel in pixels.pixels_mut() {
+            let [r, g, b, a] = pixel.0;
+            let premultiply = |channel: u8| ((channel as u32 * a as u32 + 127) / 255) as u8;
+            pixel.0 = [premultiply(r), premultiply(g), premultiply(b), a];
+        }
+    }
+    let (width, height) = pixels.dimensions();
+    // ni la page ni sa texture ne sont connues de l'atlas, `ReplacementPage` les libère
+    let texture = Box::into_raw(Box::new(SpineTexture::NeedsToBeUploaded(pixels)));
+    unsafe {
+        let region = &mut *region.c_ptr();
+        let page = Box::into_raw(Box::new(spAtlasPage {
+            rendererObject: texture as *mut _,
+            width: width as i32,
+            height: height as i32,
+            next: null_mut(),
+            ..*region.page
+        }));
+        region.page = page;
+
+        let texture_region = &mut region.super_0;
+        texture_region.u = 0.;
+        texture_region.v = 0.;
+        texture_region.u2 = 1.;
+        texture_region.v2 = 1.;
+        texture_region.degrees = 0;
+        texture_region.offsetX = 0.;
+        texture_region.offsetY = 0.;
+        texture_region.width = texture_region.originalWidth;
+        texture_region.height = texture_region.originalHeight;
+
+        ReplacementPage {
+            page,
+            texture,
+            texture_delete_queue: texture_delete_queue.clone(),
+        }
+    }
+}
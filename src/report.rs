@@ -0,0 +1,106 @@
+use glam::{Mat4, Vec2};
+use serde::Serialize;
+use std::io::Write;
+
+/// Axis aligned rectangle, either in skeleton (world) units or in image pixels.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Bounds {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Bounds {
+    /// Smallest rectangle containing every point, or `None` if there are no points.
+    pub fn from_points(points: impl IntoIterator<Item = Vec2>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        let (min, max) = points.fold((first, first), |(min, max), p| (min.min(p), max.max(p)));
+        Some(Self {
+            x: min.x,
+            y: min.y,
+            width: max.x - min.x,
+            height: max.y - min.y,
+        })
+    }
+
+    /// Projects these world bounds into image space (origin top-left, y down) using the
+    /// combined `view * world` matrix of the render.
+    pub fn to_image(self, view_world: Mat4, image_size: Vec2) -> Self {
+        let corners = [
+            Vec2::new(self.x, self.y),
+            Vec2::new(self.x + self.width, self.y),
+            Vec2::new(self.x, self.y + self.height),
+            Vec2::new(self.x + self.width, self.y + self.height),
+        ];
+        Self::from_points(
+            corners
+                .into_iter()
+                .map(|corner| project_to_image(view_world, image_size, corner)),
+        )
+        .unwrap()
+    }
+}
+
+/// Projects a skeleton point into image space (origin top-left, y down).
+pub fn project_to_image(view_world: Mat4, image_size: Vec2, point: Vec2) -> Vec2 {
+    let ndc = view_world.project_point3(point.extend(0.));
+    Vec2::new(
+        (ndc.x + 1.) * 0.5 * image_size.x,
+        (1. - ndc.y) * 0.5 * image_size.y,
+    )
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct SkeletonBounds {
+    pub world: Bounds,
+    pub image: Bounds,
+}
+
+/// One skeleton of the rendered image.
+#[derive(Debug, Serialize)]
+pub struct SkeletonReport {
+    pub skeleton: String,
+    /// `None` when nothing visible was drawn.
+    pub bounds: Option<SkeletonBounds>,
+    pub skins: Vec<String>,
+    /// Empty for the setup pose.
+    pub tracks: Vec<TrackReport>,
+    pub atlas_pages: Vec<String>,
+}
+
+/// An animation playing on a track when the frame was rendered.
+#[derive(Debug, Serialize)]
+pub struct TrackReport {
+    pub track: usize,
+    pub animation: String,
+    pub time: f32,
+}
+
+/// Metadata describing one rendered image, written next to it for asset pipelines.
+#[derive(Debug, Serialize)]
+pub struct RenderReport {
+    pub output: String,
+    pub width: u32,
+    pub height: u32,
+    /// Union of the skeleton bounds in image space, `None` when nothing visible was drawn.
+    pub bounds: Option<Bounds>,
+    /// In drawing order.
+    pub skeletons: Vec<SkeletonReport>,
+    pub render_duration_ms: f64,
+}
+
+impl RenderReport {
+    /// Writes the report as pretty JSON to `path`, or to stdout when `path` is `-`.
+    pub fn write(&self, path: &str) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        if path == "-" {
+            let mut stdout = std::io::stdout().lock();
+            writeln!(stdout, "{json}")?;
+        } else {
+            std::fs::write(path, json + "\n")?;
+        }
+        Ok(())
+    }
+}
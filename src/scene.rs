@@ -0,0 +1,315 @@
+use anyhow::Context;
+use rusty_spine::Color;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Everything a render draws, read from `--scene` or built from the command line flags.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scene {
+    #[serde(default)]
+    pub canvas: Canvas,
+    pub skeletons: Vec<SkeletonInstance>,
+}
+
+/// Output image and the camera looking at the skeletons.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Canvas {
+    /// Size of the image, in pixels.
+    pub width: u32,
+    pub height: u32,
+    /// Drawn under the skeletons, transparent when absent.
+    pub background: Option<HexColor>,
+    pub camera: Camera,
+}
+
+impl Default for Canvas {
+    fn default() -> Self {
+        Self {
+            width: 800,
+            height: 800,
+            background: None,
+            camera: Camera::default(),
+        }
+    }
+}
+
+/// Point of the scene at the center of the image, its magnification and rotation.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Camera {
+    pub x: f32,
+    pub y: f32,
+    pub zoom: f32,
+    /// Turns the scene counter-clockwise around the center of the image, in degrees.
+    pub rotation: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            x: 0.,
+            y: 0.,
+            zoom: 1.,
+            rotation: 0.,
+        }
+    }
+}
+
+/// One skeleton of a [`Scene`].
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SkeletonInstance {
+    /// Skeleton JSON or binary `.skel` file.
+    pub skeleton: PathBuf,
+    pub atlas: PathBuf,
+    /// Skins composited in order, the first one being the base skin.
+    #[serde(default)]
+    pub skins: Vec<String>,
+    /// Shorthand for a looping animation on track 0.
+    #[serde(default)]
+    pub animation: Option<String>,
+    /// Animations played together, the setup pose when there are none.
+    #[serde(default)]
+    pub tracks: Vec<Track>,
+    /// Seconds into the animations at which the frame is rendered.
+    #[serde(default)]
+    pub time: f32,
+    /// Offset from the default placement, in pixels.
+    #[serde(default)]
+    pub position: [f32; 2],
+    #[serde(default)]
+    pub scale: Scale,
+    /// Counter-clockwise, in degrees.
+    #[serde(default)]
+    pub rotation: f32,
+    /// Mirrors the skeleton through its scaleX and scaleY, so that constraints and physics
+    /// see the flip.
+    #[serde(default)]
+    pub flip_x: bool,
+    #[serde(default)]
+    pub flip_y: bool,
+    /// Drawing order, higher values on top. Instances with the same value keep the file order.
+    #[serde(default)]
+    pub z: i32,
+    /// Multiplies the color of the whole skeleton.
+    #[serde(default)]
+    pub tint: Option<HexColor>,
+    /// Per slot changes applied over the animations, keyed by slot name.
+    #[serde(default)]
+    pub slots: BTreeMap<String, SlotOverride>,
+    /// Atlas regions drawn with external images, see `--replace-region`. The images are read
+    /// from disk, not through the assets of the skeleton.
+    #[serde(default)]
+    pub replace_regions: BTreeMap<String, PathBuf>,
+}
+
+/// A uniform `1.5` or per axis `[1.5, 1]` scale.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Scale {
+    Uniform(f32),
+    Axes([f32; 2]),
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Scale::Uniform(1.)
+    }
+}
+
+impl Scale {
+    pub fn xy(self) -> [f32; 2] {
+        match self {
+            Scale::Uniform(scale) => [scale, scale],
+            Scale::Axes(scale) => scale,
+        }
+    }
+}
+
+/// Parses `--scale X[,Y]`.
+impl FromStr for Scale {
+    type Err = String;
+
+    fn from_str(arg: &str) -> Result<Self, Self::Err> {
+        let parse = |number: &str| {
+            number
+                .trim()
+                .parse::<f32>()
+                .map_err(|_| format!("expected X[,Y], got `{arg}`"))
+        };
+        match arg.split_once(',') {
+            Some((x, y)) => Ok(Scale::Axes([parse(x)?, parse(y)?])),
+            None => Ok(Scale::Uniform(parse(arg)?)),
+        }
+    }
+}
+
+/// An animation set on a track of the animation state.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Track {
+    #[serde(default)]
+    pub track: usize,
+    pub animation: String,
+    #[serde(default = "default_loop", rename = "loop")]
+    pub looping: bool,
+}
+
+fn default_loop() -> bool {
+    true
+}
+
+/// Changes to a slot that hold whatever the animations key.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SlotOverride {
+    /// Attachment shown in the slot, from the skeleton's skins.
+    pub attachment: Option<String>,
+    /// Shows no attachment at all.
+    pub hidden: bool,
+    /// Replaces the slot color.
+    pub color: Option<HexColor>,
+}
+
+/// A `#rrggbb` or `#rrggbbaa` color.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub struct HexColor(pub Color);
+
+impl TryFrom<String> for HexColor {
+    type Error = String;
+
+    fn try_from(hex: String) -> Result<Self, Self::Error> {
+        let digits = hex.strip_prefix('#').unwrap_or(&hex);
+        let channel = |index: usize| {
+            digits
+                .get(index * 2..index * 2 + 2)
+                .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+                .map(|channel| channel as f32 / 255.)
+        };
+        match (digits.len(), channel(0), channel(1), channel(2)) {
+            (6 | 8, Some(r), Some(g), Some(b)) => {
+                let a = if digits.len() == 8 {
+                    channel(3)
+                } else {
+                    Some(1.)
+                };
+                a.map(|a| HexColor(Color::new_rgba(r, g, b, a)))
+                    .ok_or_else(|| format!("invalid color `{hex}`"))
+            }
+            _ => Err(format!(
+                "invalid color `{hex}`, expected #rrggbb or #rrggbbaa"
+            )),
+        }
+    }
+}
+
+impl SkeletonInstance {
+    /// `skeleton` with every optional field left to its default, as a scene giving only the
+    /// skeleton and atlas reads it.
+    pub fn new(skeleton: PathBuf, atlas: PathBuf) -> Self {
+        Self {
+            skeleton,
+            atlas,
+            skins: vec![],
+            animation: None,
+            tracks: vec![],
+            time: 0.,
+            position: [0., 0.],
+            scale: Scale::default(),
+            rotation: 0.,
+            flip_x: false,
+            flip_y: false,
+            z: 0,
+            tint: None,
+            slots: BTreeMap::new(),
+            replace_regions: BTreeMap::new(),
+        }
+    }
+
+    /// The tracks to play, `animation` included.
+    pub fn all_tracks(&self) -> Vec<Track> {
+        let shorthand = self.animation.iter().map(|animation| Track {
+            track: 0,
+            animation: animation.clone(),
+            looping: true,
+        });
+        shorthand.chain(self.tracks.iter().cloned()).collect()
+    }
+}
+
+impl Scene {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read scene {}", path.display()))?;
+        let scene: Scene = serde_json::from_str(&json)
+            .with_context(|| format!("invalid scene {}", path.display()))?;
+        Ok(scene)
+    }
+
+    /// Checks what can be checked without loading the skeletons: names of skins, animations,
+    /// slots and attachments are checked when each skeleton is loaded.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let Canvas {
+            width,
+            height,
+            camera,
+            ..
+        } = self.canvas;
+        if width == 0 || height == 0 {
+            anyhow::bail!("canvas size must not be zero, got {width}x{height}");
+        }
+        if camera.zoom <= 0. || !camera.zoom.is_finite() {
+            anyhow::bail!("camera zoom must be positive, got {}", camera.zoom);
+        }
+        if ![camera.x, camera.y, camera.rotation]
+            .iter()
+            .all(|value| value.is_finite())
+        {
+            anyhow::bail!("invalid camera position or rotation");
+        }
+        if self.skeletons.is_empty() {
+            anyhow::bail!("scene has no skeletons");
+        }
+        for instance in &self.skeletons {
+            let name = instance.skeleton.display();
+            let scale = instance.scale.xy();
+            if scale.iter().any(|&scale| scale == 0. || !scale.is_finite()) {
+                anyhow::bail!("{name}: invalid scale {scale:?}");
+            }
+            if !instance.rotation.is_finite() {
+                anyhow::bail!("{name}: invalid rotation {}", instance.rotation);
+            }
+            if instance.time < 0. || !instance.time.is_finite() {
+                anyhow::bail!("{name}: invalid time {}", instance.time);
+            }
+            let mut used_tracks = HashSet::new();
+            for track in instance.all_tracks() {
+                if !used_tracks.insert(track.track) {
+                    anyhow::bail!("{name}: track {} is set twice", track.track);
+                }
+            }
+            for (slot, slot_override) in &instance.slots {
+                if slot_override.hidden && slot_override.attachment.is_some() {
+                    anyhow::bail!("{name}: slot {slot:?} is both hidden and given an attachment");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolves relative skeleton, atlas and image paths against `dir`, the scene's directory.
+    pub fn relative_to(&mut self, dir: &Path) {
+        for instance in &mut self.skeletons {
+            instance.skeleton = dir.join(&instance.skeleton);
+            instance.atlas = dir.join(&instance.atlas);
+            for image in instance.replace_regions.values_mut() {
+                *image = dir.join(&*image);
+            }
+        }
+    }
+}
@@ -0,0 +1,397 @@
+use base64::prelude::{Engine, BASE64_STANDARD};
+use image::RgbaImage;
+use miniquad::{Context, EventHandler};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Cursor, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::exit;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::assets::AssetResolver;
+use crate::report::RenderReport;
+use crate::scene::{Canvas, HexColor, Scene, SkeletonInstance};
+use crate::spine::{LoadOptions, Stage};
+
+/// How long a frame of the window waits for a job before drawing anyway.
+const JOB_WAIT: Duration = Duration::from_millis(50);
+/// Largest canvas side a request may ask for, in pixels.
+const MAX_SIZE: u32 = 4096;
+
+/// A scene to render for a client, and where to send the result.
+pub struct Job {
+    pub scene: Scene,
+    pub reply: Sender<anyhow::Result<(RgbaImage, RenderReport)>>,
+}
+
+/// Renders the [`Job`]s of the server or daemon threads one at a time, on the thread owning the GL
+/// context. Skeleton data, atlases and textures are kept by the [`Stage`] between jobs.
+pub struct Service {
+    stage: Stage,
+    jobs: Receiver<Job>,
+    assets: &'static dyn AssetResolver,
+    options: LoadOptions,
+}
+
+impl Service {
+    pub fn new(
+        stage: Stage,
+        jobs: Receiver<Job>,
+        assets: &'static dyn AssetResolver,
+        options: LoadOptions,
+    ) -> Self {
+        Self {
+            stage,
+            jobs,
+            assets,
+            options,
+        }
+    }
+}
+
+impl EventHandler for Service {
+    fn update(&mut self, _ctx: &mut Context) {}
+
+    fn draw(&mut self, ctx: &mut Context) {
+        match self.jobs.recv_timeout(JOB_WAIT) {
+            Ok(job) => {
+                let result = self
+                    .stage
+                    .render_scene(ctx, &job.scene, self.assets, &self.options);
+                // the client may have gone away in the meantime
+                let _ = job.reply.send(result);
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            // the daemon's stdin was closed
+            Err(RecvTimeoutError::Disconnected) => exit(0),
+        }
+        ctx.begin_default_pass(Default::default());
+        ctx.clear(Some((0.1, 0.1, 0.1, 1.0)), None, None);
+        ctx.end_render_pass();
+        ctx.commit_frame();
+    }
+}
+
+/// Answers the requests of `server`, each on its own thread, sending their scenes to the
+/// [`Service`] through `jobs`.
+pub fn listen(server: Server, jobs: Sender<Job>) {
+    for request in server.incoming_requests() {
+        let jobs = jobs.clone();
+        std::thread::spawn(move || respond(request, &jobs));
+    }
+}
+
+fn respond(request: Request, jobs: &Sender<Job>) {
+    let url = request.url().to_owned();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let response = match (request.method().as_str(), path) {
+        ("GET", "/render") => match parse_query(query).and_then(|scene| {
+            scene.validate().map_err(|err| format!("{err:#}"))?;
+            Ok(scene)
+        }) {
+            Ok(scene) => render(scene, jobs),
+            Err(message) => text(400, message),
+        },
+        ("GET", _) => text(404, format!("no such endpoint: {path}")),
+        _ => text(405, "only GET is supported".to_owned()),
+    };
+    if let Err(err) = request.respond(response) {
+        eprintln!("failed to answer {url}: {err}");
+    }
+}
+
+/// Has the [`Service`] render `scene` and encodes the frame as PNG.
+fn render(scene: Scene, jobs: &Sender<Job>) -> Response<Cursor<Vec<u8>>> {
+    let (reply, result) = mpsc::channel();
+    if jobs.send(Job { scene, reply }).is_err() {
+        return text(503, "the renderer has stopped".to_owned());
+    }
+    let image = match result.recv() {
+        Ok(Ok((image, _))) => image,
+        Ok(Err(err)) => return text(422, format!("{err:#}")),
+        Err(_) => return text(503, "the renderer has stopped".to_owned()),
+    };
+    match encode_png(&image) {
+        Ok(png) => Response::from_data(png).with_header(header("image/png")),
+        Err(err) => text(500, format!("{err:#}")),
+    }
+}
+
+fn encode_png(image: &RgbaImage) -> anyhow::Result<Vec<u8>> {
+    let mut png = Cursor::new(vec![]);
+    image
+        .write_to(&mut png, image::ImageFormat::Png)
+        .map_err(|err| anyhow::anyhow!("failed to encode PNG: {err}"))?;
+    Ok(png.into_inner())
+}
+
+fn text(status: u16, message: String) -> Response<Cursor<Vec<u8>>> {
+    Response::from_data(message.into_bytes())
+        .with_status_code(status)
+        .with_header(header("text/plain; charset=utf-8"))
+}
+
+fn header(content_type: &str) -> Header {
+    Header::from_bytes("Content-Type", content_type).expect("valid header")
+}
+
+/// Builds the single skeleton scene of a `/render` query, from its `skeleton`, `atlas`, `skins`,
+/// `animation`, `t`, `w`, `h`, `zoom` and `background` parameters. Only `skeleton` is required;
+/// the atlas defaults to the skeleton path with an `.atlas` extension.
+fn parse_query(query: &str) -> Result<Scene, String> {
+    let mut skeleton = None;
+    let mut atlas = None;
+    let mut skins = vec![];
+    let mut animation = None;
+    let mut time = 0.;
+    let mut canvas = Canvas::default();
+    for (name, value) in form_urlencoded::parse(query.as_bytes()) {
+        match &*name {
+            "skeleton" => skeleton = Some(asset_path(&value)?),
+            "atlas" => atlas = Some(asset_path(&value)?),
+            "skins" => {
+                skins = value
+                    .split(',')
+                    .filter(|skin| !skin.is_empty())
+                    .map(str::to_owned)
+                    .collect();
+            }
+            "animation" => animation = Some(value.into_owned()).filter(|name| !name.is_empty()),
+            "t" => time = number(&name, &value)?,
+            "w" => canvas.width = size(&name, &value)?,
+            "h" => canvas.height = size(&name, &value)?,
+            "zoom" => canvas.camera.zoom = number(&name, &value)?,
+            "background" => canvas.background = Some(HexColor::try_from(value.into_owned())?),
+            _ => return Err(format!("unknown parameter `{name}`")),
+        }
+    }
+    let skeleton = skeleton.ok_or("missing parameter `skeleton`")?;
+    let atlas = atlas.unwrap_or_else(|| skeleton.with_extension("atlas"));
+    Ok(Scene {
+        canvas,
+        skeletons: vec![SkeletonInstance {
+            skins,
+            animation,
+            time,
+            ..SkeletonInstance::new(skeleton, atlas)
+        }],
+    })
+}
+
+/// A path relative to the served assets, which must not leave them.
+fn asset_path(value: &str) -> Result<PathBuf, String> {
+    let path = Path::new(value);
+    match path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        true => Ok(path.to_owned()),
+        false => Err(format!(
+            "`{value}` must be relative to the assets, without `..`"
+        )),
+    }
+}
+
+fn number(name: &str, value: &str) -> Result<f32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid number `{value}` for `{name}`"))
+}
+
+fn size(name: &str, value: &str) -> Result<u32, String> {
+    match value.parse() {
+        Ok(size) if size <= MAX_SIZE => Ok(size),
+        _ => Err(format!(
+            "`{name}` must be a size of at most {MAX_SIZE} pixels, got `{value}`"
+        )),
+    }
+}
+
+/// A line of the daemon's stdin.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DaemonJob {
+    /// Echoed in the result, to match results with jobs.
+    #[serde(default)]
+    id: serde_json::Value,
+    scene: Scene,
+    /// Where to write the PNG. Without it, the PNG is returned in base64.
+    #[serde(default)]
+    out: Option<PathBuf>,
+}
+
+/// A line of the daemon's stdout: the [`RenderReport`] of the job, or its error.
+#[derive(Serialize)]
+struct DaemonResult {
+    id: serde_json::Value,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    report: Option<RenderReport>,
+    /// Base64 PNG, when the job has no `out`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Reads the daemon's jobs from stdin, one JSON object per line, has the [`Service`] render
+/// them in order and writes one result line per job to stdout. Returns when stdin is closed.
+pub fn answer_stdin(jobs: Sender<Job>) {
+    let stdout = std::io::stdout();
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result = match serde_json::from_str::<DaemonJob>(&line) {
+            Ok(job) => {
+                let id = job.id.clone();
+                run_daemon_job(job, &jobs).unwrap_or_else(|err| DaemonResult {
+                    id,
+                    report: None,
+                    image: None,
+                    error: Some(format!("{err:#}")),
+                })
+            }
+            Err(err) => DaemonResult {
+                id: serde_json::Value::Null,
+                report: None,
+                image: None,
+                error: Some(format!("invalid job: {err}")),
+            },
+        };
+        let json = serde_json::to_string(&result).expect("serializable result");
+        let mut stdout = stdout.lock();
+        if writeln!(stdout, "{json}")
+            .and_then(|()| stdout.flush())
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+fn run_daemon_job(job: DaemonJob, jobs: &Sender<Job>) -> anyhow::Result<DaemonResult> {
+    let mut scene = job.scene;
+    scene.validate()?;
+    scene.skeletons.sort_by_key(|instance| instance.z);
+
+    let (reply, result) = mpsc::channel();
+    jobs.send(Job { scene, reply })
+        .map_err(|_| anyhow::anyhow!("the renderer has stopped"))?;
+    let (image, mut report) = result
+        .recv()
+        .map_err(|_| anyhow::anyhow!("the renderer has stopped"))??;
+
+    let image = match &job.out {
+        Some(out) => {
+            image
+                .save(out)
+                .map_err(|err| anyhow::anyhow!("failed to write {}: {err}", out.display()))?;
+            report.output = out.display().to_string();
+            None
+        }
+        None => {
+            report.output = "-".to_owned();
+            Some(BASE64_STANDARD.encode(encode_png(&image)?))
+        }
+    };
+    Ok(DaemonResult {
+        id: job.id,
+        report: Some(report),
+        image,
+        error: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asset_path_stays_inside_the_assets() {
+        assert_eq!(
+            asset_path("hero/hero.json"),
+            Ok(PathBuf::from("hero/hero.json"))
+        );
+        assert_eq!(
+            asset_path("./hero/hero.json"),
+            Ok(PathBuf::from("./hero/hero.json"))
+        );
+        for value in [
+            "..",
+            "../secret.json",
+            "hero/../../secret.json",
+            "/etc/passwd",
+        ] {
+            assert!(asset_path(value).is_err(), "{value} was accepted");
+        }
+    }
+
+    #[test]
+    fn parse_query_defaults() {
+        let scene = parse_query("skeleton=hero/hero.json").unwrap();
+        assert_eq!(scene.canvas.width, Canvas::default().width);
+        assert_eq!(scene.canvas.height, Canvas::default().height);
+        assert_eq!(scene.canvas.camera.zoom, 1.);
+        assert!(scene.canvas.background.is_none());
+        let [instance] = &scene.skeletons[..] else {
+            panic!("expected one skeleton, got {}", scene.skeletons.len());
+        };
+        assert_eq!(instance.atlas, PathBuf::from("hero/hero.atlas"));
+        assert!(instance.skins.is_empty());
+        assert!(instance.animation.is_none());
+        assert_eq!(instance.time, 0.);
+    }
+
+    #[test]
+    fn parse_query_reads_every_parameter() {
+        let scene = parse_query(
+            "skeleton=hero.json&atlas=shared%2Fhero.atlas&skins=Base,,Armor&animation=idle&t=0.5\
+             &w=512&h=256&zoom=2&background=%23ff000080",
+        )
+        .unwrap();
+        assert_eq!((scene.canvas.width, scene.canvas.height), (512, 256));
+        assert_eq!(scene.canvas.camera.zoom, 2.);
+        assert_eq!(scene.canvas.background.unwrap().0.a, 128. / 255.);
+        let instance = &scene.skeletons[0];
+        assert_eq!(instance.atlas, PathBuf::from("shared/hero.atlas"));
+        assert_eq!(instance.skins, ["Base", "Armor"]);
+        assert_eq!(instance.animation.as_deref(), Some("idle"));
+        assert_eq!(instance.time, 0.5);
+    }
+
+    #[test]
+    fn parse_query_rejects_bad_parameters() {
+        for query in [
+            "",
+            "atlas=hero.atlas",
+            "skeleton=hero.json&frame=3",
+            "skeleton=../hero.json",
+            "skeleton=hero.json&atlas=/tmp/hero.atlas",
+            "skeleton=hero.json&t=soon",
+            "skeleton=hero.json&background=red",
+        ] {
+            assert!(parse_query(query).is_err(), "{query} was accepted");
+        }
+    }
+
+    #[test]
+    fn parse_query_limits_the_size() {
+        let scene = parse_query(&format!("skeleton=hero.json&w={MAX_SIZE}&h={MAX_SIZE}")).unwrap();
+        assert_eq!(
+            (scene.canvas.width, scene.canvas.height),
+            (MAX_SIZE, MAX_SIZE)
+        );
+        for query in [
+            format!("skeleton=hero.json&w={}", MAX_SIZE + 1),
+            format!("skeleton=hero.json&h={}", MAX_SIZE + 1),
+            "skeleton=hero.json&w=-1".to_owned(),
+            "skeleton=hero.json&h=big".to_owned(),
+        ] {
+            assert!(parse_query(&query).is_err(), "{query} was accepted");
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use clap::ValueEnum;
+use image::RgbaImage;
+use rusty_spine::SkeletonData;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::compare::{distances, heatmap, Metric};
+
+/// How `diff-skeletons` lays out the renders of the two versions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DiffLayout {
+    /// Old version on the left, new version on the right
+    SideBySide,
+    /// Old version faded to gray, with the pixels the new version changes from yellow to red
+    Overlay,
+}
+
+/// Names of what a skeleton defines, compared between two versions.
+#[derive(Debug, Default)]
+pub struct SkeletonOutline {
+    bones: BTreeSet<String>,
+    slots: BTreeSet<String>,
+    skins: BTreeSet<String>,
+    /// `skin/slot/attachment`.
+    attachments: BTreeSet<String>,
+    animations: BTreeSet<String>,
+}
+
+impl SkeletonOutline {
+    pub fn new(data: &SkeletonData) -> Self {
+        let mut outline = SkeletonOutline {
+            bones: data.bones().map(|bone| bone.name().to_owned()).collect(),
+            slots: data.slots().map(|slot| slot.name().to_owned()).collect(),
+            animations: data
+                .animations()
+                .map(|animation| animation.name().to_owned())
+                .collect(),
+            ..Default::default()
+        };
+        for skin in data.skins() {
+            outline.skins.insert(skin.name().to_owned());
+            for entry in skin.attachments() {
+                let slot = data
+                    .slot_at_index(entry.slot_index as usize)
+                    .map(|slot| slot.name().to_owned())
+                    .unwrap_or_default();
+                outline.attachments.insert(format!(
+                    "{}/{slot}/{}",
+                    skin.name(),
+                    entry.attachment.name()
+                ));
+            }
+        }
+        outline
+    }
+}
+
+/// Names added and removed by the new version.
+#[derive(Debug, Default, Serialize)]
+pub struct Changes {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl Changes {
+    fn between(old: &BTreeSet<String>, new: &BTreeSet<String>) -> Self {
+        Self {
+            added: new.difference(old).cloned().collect(),
+            removed: old.difference(new).cloned().collect(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Structural differences between two versions of a skeleton, written by `diff-skeletons`.
+#[derive(Debug, Serialize)]
+pub struct StructuralDiff {
+    pub bones: Changes,
+    pub slots: Changes,
+    pub skins: Changes,
+    /// `skin/slot/attachment`.
+    pub attachments: Changes,
+    pub animations: Changes,
+}
+
+impl StructuralDiff {
+    pub fn between(old: &SkeletonOutline, new: &SkeletonOutline) -> Self {
+        Self {
+            bones: Changes::between(&old.bones, &new.bones),
+            slots: Changes::between(&old.slots, &new.slots),
+            skins: Changes::between(&old.skins, &new.skins),
+            attachments: Changes::between(&old.attachments, &new.attachments),
+            animations: Changes::between(&old.animations, &new.animations),
+        }
+    }
+
+    fn sections(&self) -> [(&'static str, &Changes); 5] {
+        [
+            ("Bones", &self.bones),
+            ("Slots", &self.slots),
+            ("Skins", &self.skins),
+            ("Attachments", &self.attachments),
+            ("Animations", &self.animations),
+        ]
+    }
+
+    pub fn write_json(&self, path: &str) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        match path {
+            "-" => println!("{json}"),
+            path => std::fs::write(path, json + "\n")?,
+        }
+        Ok(())
+    }
+}
+
+/// One line per added (`+`) or removed (`-`) name, under the heading of its kind.
+impl fmt::Display for StructuralDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self
+            .sections()
+            .iter()
+            .all(|(_, changes)| changes.is_empty())
+        {
+            return writeln!(f, "No structural differences");
+        }
+        for (title, changes) in self.sections() {
+            if changes.is_empty() {
+                continue;
+            }
+            writeln!(f, "{title}:")?;
+            for name in &changes.added {
+                writeln!(f, "  + {name}")?;
+            }
+            for name in &changes.removed {
+                writeln!(f, "  - {name}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lays out the renders of the two versions, which have the same size, and counts the pixels
+/// that differ between them.
+pub fn compose(layout: DiffLayout, old: &RgbaImage, new: &RgbaImage) -> (RgbaImage, usize) {
+    let distances = distances(Metric::Channel, new, old);
+    let differing = distances.iter().filter(|&&distance| distance > 0.).count();
+    let image = match layout {
+        DiffLayout::SideBySide => {
+            let mut image = RgbaImage::new(old.width() * 2, old.height());
+            image::imageops::replace(&mut image, old, 0, 0);
+            image::imageops::replace(&mut image, new, old.width() as i64, 0);
+            image
+        }
+        DiffLayout::Overlay => heatmap(old, &distances, 0.),
+    };
+    (image, differing)
+}
@@ -1,67 +1,339 @@
-use glam::{Mat4, Vec2};
-use image::{ImageBuffer, Rgba};
+use anyhow::Context as _;
+use glam::{Mat4, Vec2, Vec3};
+use image::{ImageBuffer, RgbaImage};
 use miniquad::*;
 use miniquad::{FilterMode, RenderPass, TextureFormat, TextureParams, TextureWrap};
 use rusty_spine::{
+    atlas::{AtlasFilter, AtlasFormat},
     controller::{SkeletonController, SkeletonControllerSettings},
-    draw::{ColorSpace, CullDirection},
-    AnimationEvent, AnimationStateData, Atlas, BlendMode, Color, Physics, SkeletonBinary,
-    SkeletonJson, Skin,
+    draw::CullDirection,
+    AnimationEvent, AnimationStateData, Atlas, BlendMode, Color, Physics, Skeleton, SkeletonBinary,
+    SkeletonData, SkeletonJson, Skin,
 };
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
 use std::process::exit;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::assets::{parent_dir, AssetResolver};
+use crate::compare::Comparison;
+use crate::compose::{CanvasImage, CanvasLayers, ImageFit};
+use crate::debug::{build_overlay, DebugLayer};
+use crate::effects::Effects;
+use crate::hitmap::HitMap;
+use crate::mask::{RenderMode, SlotLegend};
+use crate::metadata::{sha256, Provenance, SkeletonProvenance, TrackProvenance};
+use crate::output::{
+    convert_readback, write_image, AlphaMode, ColorSpaceMode, Encoding, OutputFormat, Readback,
+};
+use crate::path_str;
+use crate::reload::AssetWatcher;
+use crate::replace::{replace_region, RegionReplacement, ReplacementPage};
+use crate::report::{Bounds, RenderReport, SkeletonBounds, SkeletonReport, TrackReport};
+use crate::scene::{Camera, Canvas, Scene, SkeletonInstance, SlotOverride, Track};
+use crate::texture;
+use crate::viewer::{Command, Viewer, HELP};
 
 const MAX_MESH_VERTICES: usize = 10000;
 const MAX_MESH_INDICES: usize = 5000;
 
 /// Holds all data related to load and demonstrate a particular Spine skeleton.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone)]
 pub struct SpineInfo {
-    pub atlas_path: &'static str,
+    /// Where the skeleton, atlas and page files are read from.
+    pub assets: &'static dyn AssetResolver,
+    pub atlas_path: String,
     pub skeleton_path: SpineSkeletonPath,
-    pub animation: &'static str,
+    /// Animations played together, the setup pose when empty.
+    pub tracks: Vec<Track>,
+    /// Seconds into the animations at which rendering starts.
+    pub time: f32,
     pub position: Vec2,
-    pub scale: f32,
-    pub skin: Option<&'static Skin>,
+    pub scale: Vec2,
+    /// Counter-clockwise, in degrees.
+    pub rotation: f32,
+    /// Applied through the skeleton's scaleX and scaleY.
+    pub flip_x: bool,
+    pub flip_y: bool,
+    /// Skins composited into the skin of the skeleton, as requested on the command line.
+    pub skin_names: Vec<String>,
     pub backface_culling: bool,
+    /// Overlays drawn on top of the skeleton, empty to disable.
+    pub debug: &'static [DebugLayer],
+    /// Overrides the premultiplied alpha setting read from the atlas pages, when wrong.
+    pub atlas_alpha: Option<AlphaMode>,
+    pub color_space: ColorSpaceMode,
+    /// Atlas regions drawn with external images.
+    pub region_replacements: Vec<RegionReplacement>,
+    /// Multiplies the color of the whole skeleton.
+    pub tint: Option<Color>,
+    /// Slot changes reapplied after every animation update, keyed by slot name.
+    pub slot_overrides: BTreeMap<String, SlotOverride>,
+}
+
+/// Render options shared by every skeleton of a scene.
+#[derive(Clone, Copy)]
+pub struct LoadOptions {
+    pub debug: &'static [DebugLayer],
+    pub atlas_alpha: Option<AlphaMode>,
+    pub color_space: ColorSpaceMode,
+}
+
+/// Reads an atlas and the skeleton data using it, JSON or binary (`.skel`).
+pub fn read_skeleton_data(
+    assets: &dyn AssetResolver,
+    skeleton_path: &str,
+    atlas_path: &str,
+) -> anyhow::Result<(Arc<Atlas>, Arc<SkeletonData>)> {
+    // Charger l’atlas Spine
+    let atlas = Arc::new(Atlas::new(
+        &assets.read(atlas_path)?,
+        parent_dir(atlas_path),
+    )?);
+
+    // Lire le squelette, JSON ou binaire (.skel)
+    let skeleton_bytes = assets.read(skeleton_path)?;
+    let skeleton_data = Arc::new(if is_binary_skeleton(skeleton_path) {
+        SkeletonBinary::new(atlas.clone()).read_skeleton_data(&skeleton_bytes)?
+    } else {
+        SkeletonJson::new(atlas.clone()).read_skeleton_data(&skeleton_bytes)?
+    });
+    Ok((atlas, skeleton_data))
+}
+
+/// Composites `skins` of `skeleton_data` into one skin, the first one being the base. Spine
+/// counts the references to the attachments it holds, so the skin may outlive the skeleton data,
+/// but it must not be dropped while a skeleton still uses it.
+fn composite_skin(skeleton_data: &SkeletonData, skins: &[String]) -> anyhow::Result<Skin> {
+    let mut composite = Skin::new("composite");
+    for skin in skins {
+        let skin = skeleton_data
+            .find_skin(skin)
+            .ok_or_else(|| anyhow::anyhow!("Skin {skin:?} not found"))?;
+        unsafe { composite.add_skin(&skin) };
+    }
+    Ok(composite)
+}
+
+fn is_binary_skeleton(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|ext| ext == "skel")
 }
 
+impl SpineInfo {
+    /// Describes a skeleton of the scene for the [`Stage`], reading its replacement images. The
+    /// names it uses are checked by [`Self::check`] once its files are read.
+    pub fn load(
+        assets: &'static dyn AssetResolver,
+        instance: &SkeletonInstance,
+        options: &LoadOptions,
+    ) -> anyhow::Result<SpineInfo> {
+        let atlas_path = path_str(&instance.atlas, "atlas")?;
+        let skeleton_path = path_str(&instance.skeleton, "skeleton")?;
+
+        // Images de remplacement, lues depuis le disque même avec --bundle ou --assets
+        let mut region_replacements = vec![];
+        for (region, image_path) in &instance.replace_regions {
+            let image = image::open(image_path)
+                .map_err(|err| anyhow::anyhow!("failed to open {}: {err}", image_path.display()))?;
+            region_replacements.push(RegionReplacement {
+                region: region.clone(),
+                image: image.to_rgba8(),
+            });
+        }
+
+        Ok(SpineInfo {
+            assets,
+            atlas_path: atlas_path.to_owned(),
+            skeleton_path: if is_binary_skeleton(skeleton_path) {
+                SpineSkeletonPath::Binary(skeleton_path.to_owned())
+            } else {
+                SpineSkeletonPath::Json(skeleton_path.to_owned())
+            },
+            tracks: instance.all_tracks(),
+            time: instance.time,
+            position: Vec2::from(instance.position),
+            scale: Vec2::from(instance.scale.xy()),
+            rotation: instance.rotation,
+            flip_x: instance.flip_x,
+            flip_y: instance.flip_y,
+            skin_names: instance.skins.clone(),
+            backface_culling: false,
+            debug: options.debug,
+            atlas_alpha: options.atlas_alpha,
+            color_space: options.color_space,
+            region_replacements,
+            tint: instance.tint.map(|tint| tint.0),
+            slot_overrides: instance.slots.clone(),
+        })
+    }
+
+    /// Checks that the skins, animations, slots, attachments and regions named by the scene
+    /// exist in the files read for this skeleton.
+    pub fn check(&self, atlas: &Atlas, skeleton_data: &Arc<SkeletonData>) -> anyhow::Result<()> {
+        let skeleton_path = self.skeleton_path.path();
+        let composite = composite_skin(skeleton_data, &self.skin_names)
+            .with_context(|| format!("in {skeleton_path}"))?;
+
+        for track in &self.tracks {
+            if skeleton_data.find_animation(&track.animation).is_none() {
+                anyhow::bail!(
+                    "Animation {:?} not found in {skeleton_path}",
+                    track.animation
+                );
+            }
+        }
+
+        // Les attachments sont cherchés comme au rendu, skin composé puis skin par défaut
+        let mut skeleton = Skeleton::new(skeleton_data.clone());
+        unsafe { skeleton.set_skin(&composite) };
+        for (slot, slot_override) in &self.slot_overrides {
+            if skeleton_data.find_slot(slot).is_none() {
+                anyhow::bail!("Slot {slot:?} not found in {skeleton_path}");
+            }
+            if let Some(attachment) = &slot_override.attachment {
+                if !skeleton.set_attachment(slot, Some(attachment)) {
+                    anyhow::bail!("Attachment {attachment:?} not found for slot {slot:?}");
+                }
+            }
+        }
+
+        for replacement in &self.region_replacements {
+            if atlas.find_region(&replacement.region).is_none() {
+                anyhow::bail!(
+                    "Region {:?} not found in {}",
+                    replacement.region,
+                    self.atlas_path
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Atlases and skeleton data read by [`Spine::load`], shared by the skeletons using the same
+/// files. The files of a skeleton replacing atlas regions are read for it alone and not kept.
+#[derive(Default)]
+pub struct SkeletonFiles {
+    /// Keyed by path, so that skeletons sharing an atlas also share its textures.
+    atlases: HashMap<String, Arc<Atlas>>,
+    /// Keyed by skeleton and atlas path.
+    skeleton_data: HashMap<(String, String), Arc<SkeletonData>>,
+}
+
+impl SkeletonFiles {
+    /// Atlas and skeleton data of `info`, read unless already loaded, with the pages made for
+    /// its replaced regions.
+    fn load(
+        &mut self,
+        info: &SpineInfo,
+        texture_delete_queue: &Arc<Mutex<Vec<Texture>>>,
+    ) -> anyhow::Result<(Arc<Atlas>, Arc<SkeletonData>, Vec<ReplacementPage>)> {
+        if !info.region_replacements.is_empty() {
+            let (atlas, replacement_pages) = load_atlas(info, texture_delete_queue)?;
+            let skeleton_data = read_skeleton(info, atlas.clone())?;
+            return Ok((atlas, skeleton_data, replacement_pages));
+        }
+        let atlas = match self.atlases.get(&info.atlas_path) {
+            Some(atlas) => atlas.clone(),
+            None => {
+                let (atlas, _) = load_atlas(info, texture_delete_queue)?;
+                self.atlases.insert(info.atlas_path.clone(), atlas.clone());
+                atlas
+            }
+        };
+        let key = (
+            info.skeleton_path.path().to_owned(),
+            info.atlas_path.clone(),
+        );
+        let skeleton_data = match self.skeleton_data.get(&key) {
+            Some(skeleton_data) => skeleton_data.clone(),
+            None => {
+                let skeleton_data = read_skeleton(info, atlas.clone())?;
+                self.skeleton_data.insert(key, skeleton_data.clone());
+                skeleton_data
+            }
+        };
+        Ok((atlas, skeleton_data, vec![]))
+    }
+}
+
+/// Holds where the rendered frame and its optional JSON report are written.
 #[derive(Clone, Copy, Debug)]
+pub struct OutputInfo {
+    pub png_path: &'static str,
+    /// Destination of the [`RenderReport`], `-` meaning stdout.
+    pub report_path: Option<&'static str>,
+    /// Destination of the bounding box [`HitMap`] JSON.
+    pub hitmap_path: Option<&'static str>,
+    /// Destination of the color-coded bounding box mask PNG.
+    pub hitmap_mask_path: Option<&'static str>,
+    pub mode: RenderMode,
+    /// Destination of the 16-bit slot ID image, decoded from the slot mask.
+    pub slot_ids_path: Option<&'static str>,
+    /// Destination of the [`SlotLegend`] JSON.
+    pub slot_legend_path: Option<&'static str>,
+    /// Alpha representation of the written image.
+    pub alpha: AlphaMode,
+    pub encoding: Encoding,
+    /// Blending space of the render, shared by every skeleton.
+    pub color_space: ColorSpaceMode,
+    /// Post-processing of the skeletons, color mode only.
+    pub effects: Effects,
+    /// Reference image the frame is checked against by `compare`.
+    pub comparison: Option<&'static Comparison>,
+    /// Renders again when the skeleton files change, instead of exiting.
+    pub watch: bool,
+    /// When the render was requested, used to measure the render duration.
+    pub started_at: Instant,
+}
+
+#[derive(Clone, Debug)]
 pub enum SpineSkeletonPath {
-    Binary(&'static str),
-    Json(&'static str),
+    Binary(String),
+    Json(String),
+}
+
+impl SpineSkeletonPath {
+    pub fn path(&self) -> &str {
+        match self {
+            SpineSkeletonPath::Binary(path) | SpineSkeletonPath::Json(path) => path,
+        }
+    }
 }
 
 pub struct Spine {
+    info: SpineInfo,
     controller: SkeletonController,
+    /// Skin composited from `info.skin_names`, declared after `controller` so that the
+    /// skeleton using it is dropped first.
+    composite: Skin,
+    /// Pages of the regions replaced in the atlas, also dropped after the skeleton.
+    _replacement_pages: Vec<ReplacementPage>,
     world: Mat4,
     cull_face: CullFace,
+    atlas_pages: Vec<String>,
+    /// Overlays currently drawn, toggled in the viewer.
+    debug: &'static [DebugLayer],
+    /// Animation and skin picked in the viewer, indices in the skeleton data. A `None` skin is
+    /// the one composited from the scene.
+    animation_index: Option<usize>,
+    skin_index: Option<usize>,
 }
 
 impl Spine {
-    pub fn load(info: SpineInfo) -> Self {
-        // Load atlas and auto-detect if the textures are premultiplied
-        let atlas = Arc::new(
-            Atlas::new_from_file(info.atlas_path)
-                .unwrap_or_else(|_| panic!("failed to load atlas file: {}", info.atlas_path)),
-        );
-        let premultiplied_alpha = atlas.pages().any(|page| page.pma());
-
-        // Load either binary or json skeleton files
-        let skeleton_data = Arc::new(match info.skeleton_path {
-            SpineSkeletonPath::Binary(path) => {
-                let skeleton_binary = SkeletonBinary::new(atlas);
-                skeleton_binary
-                    .read_skeleton_data_file(path)
-                    .unwrap_or_else(|_| panic!("failed to load binary skeleton file: {path}"))
-            }
-            SpineSkeletonPath::Json(path) => {
-                let skeleton_json = SkeletonJson::new(atlas);
-                skeleton_json
-                    .read_skeleton_data_file(path)
-                    .unwrap_or_else(|_| panic!("failed to load json skeleton file: {path}"))
-            }
-        });
+    /// Loads the skeleton described by `info`, with the atlas and skeleton data of `files`
+    /// when they are already read.
+    pub fn load(
+        info: SpineInfo,
+        files: &mut SkeletonFiles,
+        texture_delete_queue: &Arc<Mutex<Vec<Texture>>>,
+    ) -> anyhow::Result<Self> {
+        let (atlas, skeleton_data, replacement_pages) = files.load(&info, texture_delete_queue)?;
+        info.check(&atlas, &skeleton_data)?;
+        let premultiplied_alpha = premultiplied_alpha(&info, &atlas);
+        let atlas_pages = atlas.pages().map(|page| page.name().to_owned()).collect();
+        let composite = composite_skin(&skeleton_data, &info.skin_names)?;
 
         // Create animation state data from a skeleton
         // If desired, set crossfades at this point
@@ -76,7 +348,7 @@ impl Spine {
             .with_settings(SkeletonControllerSettings {
                 premultiplied_alpha,
                 cull_direction: CullDirection::CounterClockwise,
-                color_space: ColorSpace::SRGB,
+                color_space: info.color_space.into(),
             });
 
         // Listen for animation events
@@ -84,19 +356,19 @@ impl Spine {
             .animation_state
             .set_listener(|_, animation_event| match animation_event {
                 AnimationEvent::Start { track_entry } => {
-                    println!("Animation {} started!", track_entry.track_index());
+                    eprintln!("Animation {} started!", track_entry.track_index());
                 }
                 AnimationEvent::Interrupt { track_entry } => {
-                    println!("Animation {} interrupted!", track_entry.track_index());
+                    eprintln!("Animation {} interrupted!", track_entry.track_index());
                 }
                 AnimationEvent::End { track_entry } => {
-                    println!("Animation {} ended!", track_entry.track_index());
+                    eprintln!("Animation {} ended!", track_entry.track_index());
                 }
                 AnimationEvent::Complete { track_entry } => {
-                    println!("Animation {} completed!", track_entry.track_index());
+                    eprintln!("Animation {} completed!", track_entry.track_index());
                 }
                 AnimationEvent::Dispose { track_entry } => {
-                    println!("Animation {} disposed!", track_entry.track_index());
+                    eprintln!("Animation {} disposed!", track_entry.track_index());
                 }
                 AnimationEvent::Event {
                     track_entry,
@@ -109,395 +381,1235 @@ impl Spine {
                     balance,
                     ..
                 } => {
-                    println!("Animation {} event!", track_entry.track_index());
-                    println!("  Name: {name}");
-                    println!("  Integer: {int}");
-                    println!("  Float: {float}");
+                    eprintln!("Animation {} event!", track_entry.track_index());
+                    eprintln!("  Name: {name}");
+                    eprintln!("  Integer: {int}");
+                    eprintln!("  Float: {float}");
                     if !string.is_empty() {
-                        println!("  String: \"{string}\"");
+                        eprintln!("  String: \"{string}\"");
                     }
                     if !audio_path.is_empty() {
-                        println!("  Audio: \"{audio_path}\"");
-                        println!("    Volume: {volume}");
-                        println!("    Balance: {balance}");
+                        eprintln!("  Audio: \"{audio_path}\"");
+                        eprintln!("    Volume: {volume}");
+                        eprintln!("    Balance: {balance}");
                     }
                 }
             });
 
-        // Start the animation on track 0 and loop
-        controller
-            .animation_state
-            .set_animation_by_name(0, info.animation, true)
-            .unwrap_or_else(|_| panic!("failed to start animation: {}", info.animation));
+        for track in &info.tracks {
+            controller
+                .animation_state
+                .set_animation_by_name(track.track, &track.animation, track.looping)
+                .with_context(|| format!("failed to start animation: {}", track.animation))?;
+        }
+
+        unsafe { controller.skeleton.set_skin(&composite) }
+        if let Some(tint) = info.tint {
+            *controller.skeleton.color_mut() = tint;
+        }
+        if info.flip_x {
+            controller.skeleton.set_scale_x(-1.);
+        }
+        if info.flip_y {
+            controller.skeleton.set_scale_y(-1.);
+        }
 
-        // If a skin was provided, set it
-        if let Some(skin) = info.skin {
-            unsafe { controller.skeleton.set_skin(skin) }
+        if info.time > 0. {
+            controller.update(info.time, Physics::Update);
         }
 
         controller.settings.premultiplied_alpha = premultiplied_alpha;
         let mut pos = info.position;
         pos.y -= 300.0;
-        println!("Position: {:?}", pos);
-        Self {
-            controller,
+        let animation_index = info.tracks.first().and_then(|track| {
+            controller
+                .skeleton
+                .data()
+                .animations()
+                .position(|animation| animation.name() == track.animation)
+        });
+        let mut spine = Self {
             world: Mat4::from_translation(pos.extend(0.))
-                * Mat4::from_scale(Vec2::splat(info.scale).extend(1.)),
+                * Mat4::from_rotation_z(info.rotation.to_radians())
+                * Mat4::from_scale(info.scale.extend(1.)),
             cull_face: match info.backface_culling {
                 false => CullFace::Nothing,
                 true => CullFace::Back,
             },
+            debug: info.debug,
+            info,
+            controller,
+            composite,
+            _replacement_pages: replacement_pages,
+            atlas_pages,
+            animation_index,
+            skin_index: None,
+        };
+        spine.apply_slot_overrides();
+        Ok(spine)
+    }
+
+    /// Moves the time of every track by `seconds`, backwards when negative.
+    fn seek(&mut self, seconds: f32) {
+        if seconds >= 0. {
+            self.controller.update(seconds, Physics::Update);
+        } else {
+            let animation_state = &mut self.controller.animation_state;
+            for mut track_entry in animation_state.tracks_mut().flatten() {
+                let track_time = (track_entry.track_time() + seconds).max(0.);
+                track_entry.set_track_time(track_time);
+            }
+            // les clés ne couvrent pas forcément tout ce qu'a modifié le temps plus avancé
+            self.controller.skeleton.set_to_setup_pose();
+            self.controller.update(0., Physics::Reset);
+        }
+        self.apply_slot_overrides();
+    }
+
+    /// Replaces the tracks with the next (`direction` 1) or previous (-1) animation of the
+    /// skeleton data, looping on track 0.
+    fn cycle_animation(&mut self, direction: isize) {
+        let names: Vec<String> = self
+            .controller
+            .skeleton
+            .data()
+            .animations()
+            .map(|animation| animation.name().to_owned())
+            .collect();
+        let Some(index) = cycle(self.animation_index, direction, names.len()) else {
+            return;
+        };
+        self.animation_index = Some(index);
+        self.controller.animation_state.clear_tracks();
+        self.controller.skeleton.set_to_setup_pose();
+        self.controller
+            .animation_state
+            .set_animation_by_name(0, &names[index], true)
+            .unwrap_or_else(|_| panic!("failed to start animation: {}", names[index]));
+        self.controller.update(0., Physics::Reset);
+        self.apply_slot_overrides();
+        eprintln!(
+            "{}: animation {}",
+            self.info.skeleton_path.path(),
+            names[index]
+        );
+    }
+
+    /// Switches to the next (`direction` 1) or previous (-1) skin of the skeleton data, the
+    /// skin composited from the scene coming before the first one.
+    fn cycle_skin(&mut self, direction: isize) {
+        let names: Vec<String> = self
+            .controller
+            .skeleton
+            .data()
+            .skins()
+            .map(|skin| skin.name().to_owned())
+            .collect();
+        // la position 0 est le skin composé de la scène
+        let position = self.skin_index.map_or(0, |index| index + 1);
+        let position = cycle(Some(position), direction, names.len() + 1).unwrap_or(0);
+        self.skin_index = position.checked_sub(1);
+        let skeleton = &mut self.controller.skeleton;
+        let name = match self.skin_index {
+            Some(index) => {
+                skeleton
+                    .set_skin_by_name(&names[index])
+                    .unwrap_or_else(|_| panic!("failed to set skin: {}", names[index]));
+                names[index].clone()
+            }
+            None => {
+                unsafe { skeleton.set_skin(&self.composite) }
+                self.info.skin_names.join(" + ")
+            }
+        };
+        skeleton.set_slots_to_setup_pose();
+        self.controller.update(0., Physics::Reset);
+        self.apply_slot_overrides();
+        eprintln!("{}: skin {name}", self.info.skeleton_path.path());
+    }
+
+    /// Carries over the animations with their time, the skin picked in the viewer and the debug
+    /// overlays of `previous`, this skeleton before a reload. What the new files no longer have
+    /// is dropped.
+    fn restore(&mut self, previous: &Spine) {
+        self.debug = previous.debug;
+
+        if previous.skin_index.is_some() {
+            if let Some(skin) = previous.controller.skeleton.skin() {
+                let name = skin.name().to_owned();
+                let index = self
+                    .controller
+                    .skeleton
+                    .data()
+                    .skins()
+                    .position(|skin| skin.name() == name);
+                if let Some(index) = index {
+                    let _ = self.controller.skeleton.set_skin_by_name(&name);
+                    self.controller.skeleton.set_slots_to_setup_pose();
+                    self.skin_index = Some(index);
+                }
+            }
+        }
+
+        let tracks: Vec<(usize, String, bool, f32)> = previous
+            .controller
+            .animation_state
+            .tracks()
+            .flatten()
+            .map(|track_entry| {
+                (
+                    track_entry.track_index(),
+                    track_entry.animation().name().to_owned(),
+                    track_entry.looping(),
+                    track_entry.track_time(),
+                )
+            })
+            .collect();
+        let animation_state = &mut self.controller.animation_state;
+        animation_state.clear_tracks();
+        for (track, animation, looping, track_time) in &tracks {
+            if let Ok(mut track_entry) =
+                animation_state.set_animation_by_name(*track, animation, *looping)
+            {
+                track_entry.set_track_time(*track_time);
+            }
+        }
+        self.animation_index =
+            tracks
+                .iter()
+                .find(|(track, ..)| *track == 0)
+                .and_then(|(_, animation, ..)| {
+                    self.controller
+                        .skeleton
+                        .data()
+                        .animations()
+                        .position(|data| data.name() == animation)
+                });
+        self.controller.update(0., Physics::Reset);
+        self.apply_slot_overrides();
+    }
+
+    /// Files the skeleton was loaded from: skeleton, atlas and atlas pages.
+    fn files(&self) -> Vec<String> {
+        let atlas_dir = parent_dir(&self.info.atlas_path);
+        let pages = self.atlas_pages.iter().map(|page| match atlas_dir {
+            "" => page.clone(),
+            atlas_dir => format!("{atlas_dir}/{page}"),
+        });
+        [self.info.skeleton_path.path(), &self.info.atlas_path]
+            .map(str::to_owned)
+            .into_iter()
+            .chain(pages)
+            .collect()
+    }
+
+    /// Shows the `--debug` overlays, all of them when none were requested, or hides them.
+    fn toggle_debug(&mut self) {
+        self.debug = match (self.debug.is_empty(), self.info.debug.is_empty()) {
+            (true, false) => self.info.debug,
+            (true, true) => &[DebugLayer::All],
+            (false, _) => &[],
+        };
+    }
+
+    /// Applies the slot overrides of the scene over whatever the animations keyed.
+    fn apply_slot_overrides(&mut self) {
+        let skeleton = &mut self.controller.skeleton;
+        for (slot_name, slot_override) in &self.info.slot_overrides {
+            if slot_override.hidden {
+                skeleton.set_attachment(slot_name, None);
+            } else if let Some(attachment) = &slot_override.attachment {
+                skeleton.set_attachment(slot_name, Some(attachment));
+            }
+            if let Some(color) = slot_override.color {
+                if let Some(mut slot) = skeleton.find_slot_mut(slot_name) {
+                    *slot.color_mut() = color.0;
+                }
+            }
         }
     }
 }
 
+/// Index `direction` steps away from `index` among `count`, wrapping around. Without a current
+/// index, the first or last one depending on the direction.
+fn cycle(index: Option<usize>, direction: isize, count: usize) -> Option<usize> {
+    if count == 0 {
+        return None;
+    }
+    Some(match index {
+        Some(index) => (index as isize + direction).rem_euclid(count as isize) as usize,
+        None if direction > 0 => 0,
+        None => count - 1,
+    })
+}
+
+/// Reads the skeleton data of `info`, JSON or binary, with the regions of `atlas`.
+fn read_skeleton(info: &SpineInfo, atlas: Arc<Atlas>) -> anyhow::Result<Arc<SkeletonData>> {
+    // Load either binary or json skeleton files
+    Ok(Arc::new(match &info.skeleton_path {
+        SpineSkeletonPath::Binary(path) => {
+            let skeleton_binary = SkeletonBinary::new(atlas);
+            skeleton_binary
+                .read_skeleton_data(&read_skeleton_file(info.assets, path)?)
+                .with_context(|| format!("failed to load binary skeleton file: {path}"))?
+        }
+        SpineSkeletonPath::Json(path) => {
+            let skeleton_json = SkeletonJson::new(atlas);
+            skeleton_json
+                .read_skeleton_data(&read_skeleton_file(info.assets, path)?)
+                .with_context(|| format!("failed to load json skeleton file: {path}"))?
+        }
+    }))
+}
+
+/// Loads the atlas of `info` and applies its region replacements.
+fn load_atlas(
+    info: &SpineInfo,
+    texture_delete_queue: &Arc<Mutex<Vec<Texture>>>,
+) -> anyhow::Result<(Arc<Atlas>, Vec<ReplacementPage>)> {
+    let atlas_bytes = info
+        .assets
+        .read(&info.atlas_path)
+        .context("failed to read atlas file")?;
+    let atlas = Atlas::new(&atlas_bytes, parent_dir(&info.atlas_path))
+        .with_context(|| format!("failed to load atlas file: {}", info.atlas_path))?;
+    for page in atlas.pages() {
+        if let Some(texture) = unsafe { page.renderer_object().get::<SpineTexture>() } {
+            texture.decode(info.assets)?;
+        }
+    }
+    let premultiplied_alpha = premultiplied_alpha(info, &atlas);
+    let mut replacement_pages = vec![];
+    for replacement in &info.region_replacements {
+        let region = atlas
+            .find_region(&replacement.region)
+            .with_context(|| format!("region not found in atlas: {}", replacement.region))?;
+        replacement_pages.push(replace_region(
+            &region,
+            replacement,
+            premultiplied_alpha,
+            texture_delete_queue,
+        ));
+    }
+    Ok((Arc::new(atlas), replacement_pages))
+}
+
+/// Colors and IDs of the slots of `spines`, empty unless a slot mask or legend is written.
+fn slot_legend(output: &OutputInfo, spines: &[Spine]) -> SlotLegend {
+    match output.mode == RenderMode::SlotMask || output.slot_legend_path.is_some() {
+        true => SlotLegend::new(spines.iter().map(|spine| &spine.controller.skeleton)),
+        false => SlotLegend::default(),
+    }
+}
+
+/// Whether the atlas textures are premultiplied, auto-detected unless overridden.
+fn premultiplied_alpha(info: &SpineInfo, atlas: &Atlas) -> bool {
+    match info.atlas_alpha {
+        Some(atlas_alpha) => atlas_alpha == AlphaMode::Premultiplied,
+        None => atlas.pages().any(|page| page.pma()),
+    }
+}
+
+fn read_skeleton_file(assets: &dyn AssetResolver, path: &str) -> anyhow::Result<Vec<u8>> {
+    assets.read(path).context("failed to read skeleton file")
+}
+
 pub struct Stage {
-    spine: Spine,
+    /// In drawing order.
+    spines: Vec<Spine>,
+    color_space: ColorSpaceMode,
+    camera: Camera,
+    /// Camera of the scene, restored by the viewer.
+    initial_camera: Camera,
+    viewer: Viewer,
+    /// Files reloaded when they change, in the viewer.
+    watcher: Option<AssetWatcher>,
+    /// Files read by the previous [`Self::render_scene`] calls.
+    files: SkeletonFiles,
+    background: Option<Color>,
     pipeline: Pipeline,
+    mask_pipeline: Pipeline,
+    slot_legend: SlotLegend,
     bindings: Vec<Bindings>,
+    debug_bindings: Option<Bindings>,
+    debug_texture: Option<Texture>,
+    /// `--background-image` and `--overlay-image` with their textures.
+    background_image: Option<(&'static CanvasImage, Texture)>,
+    overlay_image: Option<(&'static CanvasImage, Texture)>,
+    image_bindings: Option<Bindings>,
     texture_delete_queue: Arc<Mutex<Vec<Texture>>>,
     last_frame_time: f64,
     screen_size: Vec2,
+    /// Size of the render pass, in pixels.
+    target_size: Vec2,
     render_png: bool,
+    /// Whether the outputs are written and `--watch` waits for a change.
+    rendered: bool,
     render_pass: RenderPass,
     color_texture: Texture,
-    png_path: String,
+    output: OutputInfo,
 }
 
 impl Stage {
     pub fn new(
         ctx: &mut Context,
         texture_delete_queue: Arc<Mutex<Vec<Texture>>>,
-        spine_info: &SpineInfo,
-        render_png: &bool,
-        png_path: &str,
+        canvas: &Canvas,
+        layers: &CanvasLayers,
+        spine_infos: &[SpineInfo],
+        output: &OutputInfo,
     ) -> Stage {
         // --- création de la texture et du render pass ---
         let (w_px, h_px) = (
-            (800. * ctx.dpi_scale()) as u32,
-            (800. * ctx.dpi_scale()) as u32,
-        );
-        let color_tex = Texture::new_render_texture(
-            ctx,
-            TextureParams {
-                format: TextureFormat::RGBA8,
-                wrap: TextureWrap::Clamp,
-                filter: FilterMode::Nearest,
-                width: w_px,
-                height: h_px,
-                ..Default::default()
-            },
-        );
-        let depth_tex = Texture::new_render_texture(
-            ctx,
-            TextureParams {
-                format: TextureFormat::Depth,
-                height: h_px,
-                width: w_px,
-                ..Default::default()
-            },
+            (canvas.width as f32 * ctx.dpi_scale()) as u32,
+            (canvas.height as f32 * ctx.dpi_scale()) as u32,
         );
-        let render_pass = RenderPass::new(ctx, color_tex, Some(depth_tex));
+        let (render_pass, color_tex) = create_render_target(ctx, w_px, h_px);
 
-        let spine_info = *spine_info;
-        let spine = Spine::load(spine_info);
+        let mut files = SkeletonFiles::default();
+        let spines: Vec<Spine> = spine_infos
+            .iter()
+            .map(|spine_info| {
+                Spine::load(spine_info.clone(), &mut files, &texture_delete_queue)
+                    .unwrap_or_else(|err| panic!("{err:#}"))
+            })
+            .collect();
+        let slot_legend = slot_legend(output, &spines);
+        let color_space = output.color_space;
+        let mut upload = |canvas_image: &'static CanvasImage| {
+            let texture = Texture::from_rgba8(
+                ctx,
+                canvas_image.image.width() as u16,
+                canvas_image.image.height() as u16,
+                &canvas_image.image,
+            );
+            texture.set_filter(ctx, FilterMode::Linear);
+            (canvas_image, texture)
+        };
+        let background_image = layers.background.map(&mut upload);
+        let overlay_image = layers.overlay.map(upload);
+
+        let render_png = output.png_path != "none" || output.comparison.is_some();
+        if !render_png {
+            eprintln!("{HELP}");
+        }
 
-        Stage {
-            spine,
-            pipeline: create_pipeline(ctx),
+        let mut stage = Stage {
+            spines,
+            color_space,
+            camera: canvas.camera,
+            initial_camera: canvas.camera,
+            viewer: Viewer::default(),
+            watcher: None,
+            files: SkeletonFiles::default(),
+            background: canvas.background.map(|background| background.0),
+            pipeline: create_pipeline(
+                ctx,
+                match color_space {
+                    ColorSpaceMode::Srgb => shader::FRAGMENT,
+                    ColorSpaceMode::Linear => shader::LINEAR_FRAGMENT,
+                },
+            ),
+            mask_pipeline: create_pipeline(ctx, shader::MASK_FRAGMENT),
+            slot_legend,
             bindings: vec![],
+            debug_bindings: None,
+            debug_texture: None,
+            background_image,
+            overlay_image,
+            image_bindings: None,
             texture_delete_queue,
             last_frame_time: date::now(),
             screen_size: Vec2::new(w_px as f32, h_px as f32),
-            render_png: *render_png,
+            target_size: Vec2::new(w_px as f32, h_px as f32),
+            render_png,
+            rendered: false,
             render_pass,
             color_texture: color_tex,
-            png_path: png_path.to_string(),
+            output: *output,
+        };
+        if !stage.render_png || output.watch {
+            stage.watch_files();
+        }
+        stage
+    }
+
+    /// Starts watching the files of the skeletons, for [`Self::reload`].
+    fn watch_files(&mut self) {
+        let files = self.spines.iter().flat_map(|spine| {
+            let assets = spine.info.assets;
+            spine.files().into_iter().map(move |path| (assets, path))
+        });
+        self.watcher = Some(AssetWatcher::new(files, date::now()));
+    }
+
+    /// Reloads every skeleton from its files, keeping what the viewer changed, and with
+    /// `--watch` renders the outputs again. On failure, e.g. when an export is still being
+    /// written, the previous skeletons are kept.
+    fn reload(&mut self) {
+        let mut files = SkeletonFiles::default();
+        let mut spines = Vec::with_capacity(self.spines.len());
+        for spine in &self.spines {
+            match Spine::load(spine.info.clone(), &mut files, &self.texture_delete_queue) {
+                Ok(mut reloaded) => {
+                    if !self.render_png {
+                        reloaded.restore(spine);
+                    }
+                    spines.push(reloaded);
+                }
+                Err(err) => {
+                    eprintln!("Reload failed, keeping the previous files: {err:#}");
+                    return;
+                }
+            }
+        }
+        // les anciennes textures partent dans la file de suppression en même temps que l'atlas
+        self.spines = spines;
+        self.slot_legend = slot_legend(&self.output, &self.spines);
+        self.watch_files();
+        if self.render_png {
+            self.rendered = false;
+            self.output.started_at = Instant::now();
+            eprintln!("Rebuilding {}", self.output.png_path);
+        } else {
+            eprintln!("Reloaded");
+        }
+    }
+
+    /// Renders `scene` on its own, for the render server and daemon, in place of the skeletons
+    /// given to [`Self::new`]. Atlases and skeleton data stay loaded with their textures between
+    /// calls, so only the skeletons and their animation states are built for each scene.
+    pub fn render_scene(
+        &mut self,
+        ctx: &mut Context,
+        scene: &Scene,
+        assets: &'static dyn AssetResolver,
+        options: &LoadOptions,
+    ) -> anyhow::Result<(RgbaImage, RenderReport)> {
+        self.output.started_at = Instant::now();
+        self.delete_disposed_textures();
+
+        let mut spines = Vec::with_capacity(scene.skeletons.len());
+        for instance in &scene.skeletons {
+            let info = SpineInfo::load(assets, instance, options)?;
+            spines.push(Spine::load(
+                info,
+                &mut self.files,
+                &self.texture_delete_queue,
+            )?);
+        }
+        self.spines = spines;
+        self.slot_legend = slot_legend(&self.output, &self.spines);
+        self.camera = scene.canvas.camera;
+        self.background = scene.canvas.background.map(|background| background.0);
+
+        let size = Vec2::new(scene.canvas.width as f32, scene.canvas.height as f32);
+        if size != self.target_size {
+            self.render_pass.delete(ctx);
+            (self.render_pass, self.color_texture) =
+                create_render_target(ctx, size.x as u32, size.y as u32);
+            self.target_size = size;
+        }
+        self.screen_size = size;
+        let view = self.view();
+        let (w, h) = (size.x as usize, size.y as usize);
+        let (frame, drawn_points) = self.render_frame(ctx, view, w, h);
+        let image = frame.to_rgba8(self.output.alpha);
+        let report = self.report(view, w, h, drawn_points);
+        Ok((image, report))
+    }
+
+    /// Deletes the textures of the atlas pages disposed since the last call. The delete call
+    /// needs to happen before rendering, or it may not actually delete the texture.
+    fn delete_disposed_textures(&self) {
+        for texture in self.texture_delete_queue.lock().unwrap().drain(..) {
+            texture.delete();
         }
     }
 
     fn view(&self) -> Mat4 {
-        Mat4::orthographic_rh_gl(
+        let Camera {
+            x,
+            y,
+            zoom,
+            rotation,
+        } = self.camera;
+        let half_size = self.screen_size * 0.5 / zoom;
+        Mat4::orthographic_rh_gl(-half_size.x, half_size.x, -half_size.y, half_size.y, 0., 1.)
+            * Mat4::from_rotation_z(rotation.to_radians())
+            * Mat4::from_translation(Vec3::new(-x, -y, 0.))
+    }
+
+    /// Background of the canvas as stored in the framebuffer: premultiplied, and linear in the
+    /// linear color space. The slot mask always has a transparent background.
+    fn clear_color(&self) -> (f32, f32, f32, f32) {
+        match self.background {
+            Some(background) if self.output.mode == RenderMode::Color => {
+                let background = match self.color_space {
+                    ColorSpaceMode::Srgb => background,
+                    ColorSpaceMode::Linear => background.nonlinear_to_linear(),
+                };
+                let Color { r, g, b, a } = background;
+                (r * a, g * a, b * a, a)
+            }
+            _ => (0., 0., 0., 0.),
+        }
+    }
+
+    /// Draws the skeleton at `spine_index` in the current pass and returns the world positions
+    /// of every vertex that was drawn with a texture.
+    fn draw_spine(&mut self, ctx: &mut Context, view: Mat4, spine_index: usize) -> Vec<Vec2> {
+        let spine = &mut self.spines[spine_index];
+        let renderables = spine.controller.combined_renderables();
+
+        // Create bindings that can be re-used for rendering Spine meshes
+        while renderables.len() > self.bindings.len() {
+            self.bindings.push(create_bindings(ctx));
+        }
+
+        ctx.apply_pipeline(&self.pipeline);
+
+        // Apply backface culling only if this skeleton needs it
+        ctx.set_cull_face(spine.cull_face);
+
+        let mut drawn_points = vec![];
+        for (renderable, bindings) in renderables.into_iter().zip(self.bindings.iter_mut()) {
+            // Set blend state based on this renderable's blend mode
+            let BlendStates {
+                alpha_blend,
+                color_blend,
+            } = renderable
+                .blend_mode
+                .get_blend_states(spine.controller.settings.premultiplied_alpha);
+            ctx.set_blend(Some(color_blend), Some(alpha_blend));
+
+            // Create the vertex and index buffers for miniquad
+            let mut vertices = Vec::with_capacity(renderable.vertices.len());
+            for vertex_index in 0..renderable.vertices.len() {
+                vertices.push(Vertex {
+                    position: Vec2 {
+                        x: renderable.vertices[vertex_index][0],
+                        y: renderable.vertices[vertex_index][1],
+                    },
+                    uv: Vec2 {
+                        x: renderable.uvs[vertex_index][0],
+                        y: renderable.uvs[vertex_index][1],
+                    },
+                    color: Color::from(renderable.colors[vertex_index]),
+                    dark_color: Color::from(renderable.dark_colors[vertex_index]),
+                });
+            }
+            bindings.vertex_buffers[0].update(ctx, &vertices);
+            bindings.index_buffer.update(ctx, &renderable.indices);
+
+            // If there is no attachment (and therefore no texture), skip rendering this renderable
+            // May also be None if a create texture callback was never set.
+            let Some(attachment_renderer_object) = renderable.attachment_renderer_object else {
+                continue;
+            };
+            drawn_points.extend(vertices.iter().map(|vertex| vertex.position));
+
+            // Load textures if they haven't been loaded already
+            let spine_texture = unsafe { &mut *(attachment_renderer_object as *mut SpineTexture) };
+            bindings.images = vec![spine_texture.load(ctx)];
+
+            // Draw this renderable
+            ctx.apply_bindings(bindings);
+            ctx.apply_uniforms(&shader::Uniforms {
+                world: spine.world,
+                view,
+                premultiplied: spine.controller.settings.premultiplied_alpha as u8 as f32,
+            });
+            ctx.draw(0, renderable.indices.len() as i32, 1);
+        }
+        drawn_points
+    }
+
+    /// Same as [`Self::draw_spine`] but for the slot mask pass: each slot is drawn with its
+    /// legend color, without blending, so later slots in draw order cover earlier ones.
+    fn draw_slot_mask(&mut self, ctx: &mut Context, view: Mat4, spine_index: usize) -> Vec<Vec2> {
+        let spine = &mut self.spines[spine_index];
+        let renderables = spine.controller.renderables();
+        while renderables.len() > self.bindings.len() {
+            self.bindings.push(create_bindings(ctx));
+        }
+
+        ctx.apply_pipeline(&self.mask_pipeline);
+        ctx.set_cull_face(spine.cull_face);
+        ctx.set_blend(None, None);
+
+        let mut drawn_points = vec![];
+        for (renderable, bindings) in renderables.into_iter().zip(self.bindings.iter_mut()) {
+            let Some(attachment_renderer_object) = renderable.attachment_renderer_object else {
+                continue;
+            };
+            // slot_index is the position in the draw order, the legend uses the setup order
+            let Some(slot) = spine
+                .controller
+                .skeleton
+                .draw_order_at_index(renderable.slot_index)
+            else {
+                continue;
+            };
+            let [r, g, b] = self.slot_legend.color(spine_index, slot.data().index());
+            let color = Color::new_rgba(
+                r as f32 / 255.,
+                g as f32 / 255.,
+                b as f32 / 255.,
+                renderable.color.a,
+            );
+
+            let vertices: Vec<Vertex> = renderable
+                .vertices
+                .iter()
+                .zip(renderable.uvs.iter())
+                .map(|(position, uv)| Vertex {
+                    position: Vec2::from(*position),
+                    uv: Vec2::from(*uv),
+                    color,
+                    dark_color: Color::new_rgba(0., 0., 0., 0.),
+                })
+                .collect();
+            bindings.vertex_buffers[0].update(ctx, &vertices);
+            bindings.index_buffer.update(ctx, &renderable.indices);
+            drawn_points.extend(vertices.iter().map(|vertex| vertex.position));
+
+            let spine_texture = unsafe { &mut *(attachment_renderer_object as *mut SpineTexture) };
+            bindings.images = vec![spine_texture.load(ctx)];
+
+            ctx.apply_bindings(bindings);
+            ctx.apply_uniforms(&shader::Uniforms {
+                world: spine.world,
+                view,
+                premultiplied: 0.,
+            });
+            ctx.draw(0, renderable.indices.len() as i32, 1);
+        }
+        drawn_points
+    }
+
+    /// Draws every skeleton with the selected [`RenderMode`] and returns the points drawn for
+    /// each of them, see [`Self::draw_spine`].
+    fn draw_skeletons(&mut self, ctx: &mut Context, view: Mat4) -> Vec<Vec<Vec2>> {
+        let mut drawn_points = vec![];
+        for spine_index in 0..self.spines.len() {
+            drawn_points.push(match self.output.mode {
+                RenderMode::Color => self.draw_spine(ctx, view, spine_index),
+                RenderMode::SlotMask => self.draw_slot_mask(ctx, view, spine_index),
+            });
+        }
+        drawn_points
+    }
+
+    /// Describes the frame just drawn with `view`, from the points drawn for each skeleton.
+    fn report(&self, view: Mat4, w: usize, h: usize, drawn_points: Vec<Vec<Vec2>>) -> RenderReport {
+        let image_size = Vec2::new(w as f32, h as f32);
+        let skeletons: Vec<SkeletonReport> = self
+            .spines
+            .iter()
+            .zip(drawn_points)
+            .map(|(spine, drawn_points)| SkeletonReport {
+                skeleton: spine.info.skeleton_path.path().to_owned(),
+                bounds: Bounds::from_points(drawn_points).map(|world| SkeletonBounds {
+                    world,
+                    image: world.to_image(view * spine.world, image_size),
+                }),
+                skins: spine.info.skin_names.to_vec(),
+                tracks: spine
+                    .info
+                    .tracks
+                    .iter()
+                    .map(|track| TrackReport {
+                        track: track.track,
+                        animation: track.animation.clone(),
+                        time: spine
+                            .controller
+                            .animation_state
+                            .get_current(track.track)
+                            .map_or(0., |track_entry| track_entry.animation_time()),
+                    })
+                    .collect(),
+                atlas_pages: spine.atlas_pages.clone(),
+            })
+            .collect();
+        RenderReport {
+            output: self.output.png_path.to_owned(),
+            width: w as u32,
+            height: h as u32,
+            bounds: Bounds::from_points(skeletons.iter().flat_map(|skeleton| {
+                skeleton.bounds.iter().flat_map(|bounds| {
+                    let image = bounds.image;
+                    [
+                        Vec2::new(image.x, image.y),
+                        Vec2::new(image.x + image.width, image.y + image.height),
+                    ]
+                })
+            })),
+            skeletons,
+            render_duration_ms: self.output.started_at.elapsed().as_secs_f64() * 1000.,
+        }
+    }
+
+    /// Describes what the written image is rendered from, for `--metadata`.
+    fn provenance(&self) -> anyhow::Result<Provenance> {
+        let skeletons = self
+            .spines
+            .iter()
+            .map(|spine| {
+                let path = spine.info.skeleton_path.path();
+                let bytes = read_skeleton_file(spine.info.assets, path)?;
+                Ok(SkeletonProvenance {
+                    skeleton: path.to_owned(),
+                    sha256: sha256(&bytes),
+                    atlas: spine.info.atlas_path.to_owned(),
+                    skins: spine.info.skin_names.to_vec(),
+                    tracks: spine
+                        .info
+                        .tracks
+                        .iter()
+                        .map(|track| TrackProvenance {
+                            track: track.track,
+                            animation: track.animation.clone(),
+                        })
+                        .collect(),
+                    time: spine.info.time,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Provenance::new(skeletons))
+    }
+
+    /// Draws the frame into the render pass and reads it back: background, skeletons with their
+    /// effects, overlay and debug overlays. Also returns the points drawn for each skeleton, see
+    /// [`Self::draw_spine`].
+    fn render_frame(
+        &mut self,
+        ctx: &mut Context,
+        view: Mat4,
+        w: usize,
+        h: usize,
+    ) -> (Readback, Vec<Vec<Vec2>>) {
+        // --- on dessine dans notre RenderPass au lieu du default backbuffer ---
+        // fond transparent par défaut : le framebuffer contient des couleurs prémultipliées
+        let (r, g, b, a) = self.clear_color();
+        let pass_action = miniquad::PassAction::clear_color(r, g, b, a);
+
+        // le masque de slots garde ses couleurs exactes, sans encodage sRGB
+        let linear =
+            self.color_space == ColorSpaceMode::Linear && self.output.mode == RenderMode::Color;
+
+        let effects = self.output.effects;
+        let drawn_points = if effects.is_empty() || self.output.mode != RenderMode::Color {
+            ctx.begin_pass(self.render_pass, pass_action);
+            self.draw_canvas_image(ctx, self.background_image);
+            let drawn_points = self.draw_skeletons(ctx, view);
+            self.draw_canvas_image(ctx, self.overlay_image);
+            // les couleurs du masque de slots sont des IDs, sans surcouche de debug
+            if self.output.mode == RenderMode::Color {
+                self.draw_debug(ctx, view);
+            }
+            ctx.end_render_pass();
+            drawn_points
+        } else {
+            // les effets ne s'appliquent qu'aux squelettes : ils sont capturés seuls, puis
+            // l'image traitée est redessinée entre le fond et la surcouche
+            ctx.begin_pass(
+                self.render_pass,
+                miniquad::PassAction::clear_color(0., 0., 0., 0.),
+            );
+            let drawn_points = self.draw_skeletons(ctx, view);
+            ctx.end_render_pass();
+            let mut skeletons = self.read_frame(w, h);
+            convert_readback(&mut skeletons, AlphaMode::Straight, linear);
+            let skeletons = CanvasImage {
+                image: effects.apply(&skeletons),
+                fit: ImageFit::Fit,
+            };
+            let texture = Texture::from_rgba8(ctx, w as u16, h as u16, skeletons.image.as_raw());
+
+            ctx.begin_pass(self.render_pass, pass_action);
+            self.draw_canvas_image(ctx, self.background_image);
+            self.draw_canvas_image(ctx, Some((&skeletons, texture)));
+            self.draw_canvas_image(ctx, self.overlay_image);
+            self.draw_debug(ctx, view);
+            ctx.end_render_pass();
+            texture.delete();
+            drawn_points
+        };
+
+        let frame = Readback {
+            pixels: self.read_frame(w, h),
+            linear,
+        };
+        (frame, drawn_points)
+    }
+
+    /// Reads back the `w`×`h` pixels of the render pass, top row first. They are still
+    /// premultiplied, and linear in the linear color space, see [`convert_readback`].
+    fn read_frame(&self, w: usize, h: usize) -> RgbaImage {
+        // allocate a buffer for RGBA8 bytes
+        let mut pixels = vec![0u8; w * h * 4];
+
+        // actually read them back from the GPU
+        self.color_texture.read_pixels(&mut pixels);
+
+        // flip vertically in-place
+        let line_bytes = w * 4;
+        for y in 0..(h / 2) {
+            let top_start = y * line_bytes;
+            let bot_start = (h - 1 - y) * line_bytes;
+
+            // split into [0 .. bot_start) and [bot_start .. end);
+            // these two slices are guaranteed non-overlapping
+            let (prefix, suffix) = pixels.split_at_mut(bot_start);
+
+            // within prefix, take the top row
+            let top_row = &mut prefix[top_start..top_start + line_bytes];
+            // within suffix, take the bottom row (which is at the start of suffix)
+            let bottom_row = &mut suffix[..line_bytes];
+
+            // now swap them
+            top_row.swap_with_slice(bottom_row);
+        }
+
+        // then build the ImageBuffer
+        ImageBuffer::from_raw(w as u32, h as u32, pixels).expect("Erreur création ImageBuffer")
+    }
+
+    /// Draws a background or overlay image over the whole canvas in the current pass. Nothing is
+    /// drawn in the slot mask, which only holds slots.
+    fn draw_canvas_image(&mut self, ctx: &mut Context, image: Option<(&CanvasImage, Texture)>) {
+        let Some((canvas_image, texture)) = image else {
+            return;
+        };
+        if self.output.mode != RenderMode::Color {
+            return;
+        }
+        let bindings = self
+            .image_bindings
+            .get_or_insert_with(|| create_bindings(ctx));
+        bindings.images = vec![texture];
+
+        ctx.apply_pipeline(&self.pipeline);
+        ctx.set_cull_face(CullFace::Nothing);
+        let BlendStates {
+            alpha_blend,
+            color_blend,
+        } = BlendMode::Normal.get_blend_states(false);
+        ctx.set_blend(Some(color_blend), Some(alpha_blend));
+
+        // the images stay in place whatever the camera
+        let view = Mat4::orthographic_rh_gl(
             self.screen_size.x * -0.5,
             self.screen_size.x * 0.5,
             self.screen_size.y * -0.5,
             self.screen_size.y * 0.5,
             0.,
             1.,
-        )
+        );
+        let quads = canvas_image.quads(self.screen_size);
+        for batch in quads.chunks(MAX_MESH_INDICES / 6) {
+            let mut vertices = Vec::with_capacity(batch.len() * 4);
+            let mut indices = Vec::with_capacity(batch.len() * 6);
+            for quad in batch {
+                let first = vertices.len() as u16;
+                indices.extend([0, 1, 2, 0, 2, 3].map(|i| first + i));
+                vertices.extend(
+                    quad.corners
+                        .iter()
+                        .zip(quad.uvs)
+                        .map(|(&position, uv)| Vertex {
+                            position,
+                            uv,
+                            color: Color::new_rgba(1., 1., 1., 1.),
+                            dark_color: Color::new_rgba(0., 0., 0., 0.),
+                        }),
+                );
+            }
+            bindings.vertex_buffers[0].update(ctx, &vertices);
+            bindings.index_buffer.update(ctx, &indices);
+            ctx.apply_bindings(bindings);
+            ctx.apply_uniforms(&shader::Uniforms {
+                world: Mat4::IDENTITY,
+                view,
+                premultiplied: 0.,
+            });
+            ctx.draw(0, indices.len() as i32, 1);
+        }
     }
-}
 
-impl EventHandler for Stage {
-    fn update(&mut self, _ctx: &mut Context) {
-        let now = date::now();
-        let dt = ((now - self.last_frame_time) as f32).max(0.001);
-        self.spine.controller.update(dt, Physics::Update);
-        self.last_frame_time = now;
+    /// Applies a viewer [`Command`].
+    fn run_command(&mut self, ctx: &mut Context, command: Command) {
+        match command {
+            Command::TogglePause => {
+                self.viewer.paused = !self.viewer.paused;
+                eprintln!(
+                    "{}",
+                    if self.viewer.paused {
+                        "Paused"
+                    } else {
+                        "Playing"
+                    }
+                );
+            }
+            Command::Seek(seconds) => {
+                for spine in &mut self.spines {
+                    spine.seek(seconds);
+                }
+            }
+            Command::Speed(factor) => {
+                self.viewer.set_speed(factor);
+                eprintln!("Speed: ×{}", self.viewer.speed);
+            }
+            Command::Animation(direction) => {
+                self.spines[self.viewer.selected].cycle_animation(direction);
+            }
+            Command::Skin(direction) => self.spines[self.viewer.selected].cycle_skin(direction),
+            Command::NextSkeleton => {
+                self.viewer.selected = (self.viewer.selected + 1) % self.spines.len();
+                let spine = &self.spines[self.viewer.selected];
+                eprintln!("Selected {}", spine.info.skeleton_path.path());
+            }
+            Command::ToggleDebug => {
+                for spine in &mut self.spines {
+                    spine.toggle_debug();
+                }
+            }
+            Command::Pan(delta) => {
+                // la scène suit la souris, y vers le haut dans la scène
+                let rotation = Mat4::from_rotation_z(-self.camera.rotation.to_radians());
+                let delta = rotation.transform_vector3(Vec3::new(delta.x, -delta.y, 0.));
+                self.camera.x -= delta.x / self.camera.zoom;
+                self.camera.y -= delta.y / self.camera.zoom;
+            }
+            Command::Zoom(factor) => self.camera.zoom *= factor,
+            Command::ResetCamera => self.camera = self.initial_camera,
+            Command::SaveFrame => self.save_frame(ctx),
+            Command::Help => eprintln!("{HELP}"),
+        }
     }
 
-    fn draw(&mut self, ctx: &mut Context) {
-        let renderables = self.spine.controller.combined_renderables();
+    /// Renders the current frame at the canvas size and writes it as PNG with the options of
+    /// `--out`, next to the working directory's previous frames.
+    fn save_frame(&mut self, ctx: &mut Context) {
+        let path = loop {
+            self.viewer.saved_frames += 1;
+            let path = format!("frame_{:04}.png", self.viewer.saved_frames);
+            if !Path::new(&path).exists() {
+                break path;
+            }
+        };
+        let screen_size =
+            std::mem::replace(&mut self.screen_size, self.target_size / ctx.dpi_scale());
+        let view = self.view();
+        let (w, h) = (self.target_size.x as usize, self.target_size.y as usize);
+        let (frame, _) = self.render_frame(ctx, view, w, h);
+        self.screen_size = screen_size;
+        // `--out` peut être dans un autre format, les frames restent en PNG
+        let encoding = Encoding {
+            format: match self.output.encoding.format {
+                OutputFormat::Png16 => OutputFormat::Png16,
+                _ => OutputFormat::Png,
+            },
+            ..self.output.encoding
+        };
+        let written = match encoding.metadata {
+            true => self.provenance().map(|provenance| provenance.text_chunks()),
+            false => Ok(vec![]),
+        }
+        .and_then(|text| write_image(&frame, &path, self.output.alpha, &encoding, &text));
+        match written {
+            Ok(()) => eprintln!("Frame written to {path}"),
+            Err(err) => eprintln!("failed to write {path}: {err:#}"),
+        }
+    }
 
-        // Create bindings that can be re-used for rendering Spine meshes
-        while renderables.len() > self.bindings.len() {
-            let vertex_buffer = Buffer::stream(
-                ctx,
-                BufferType::VertexBuffer,
-                MAX_MESH_VERTICES * std::mem::size_of::<Vertex>(),
-            );
-            let index_buffer = Buffer::stream(
-                ctx,
-                BufferType::IndexBuffer,
-                MAX_MESH_INDICES * std::mem::size_of::<u16>(),
-            );
-            self.bindings.push(Bindings {
-                vertex_buffers: vec![vertex_buffer],
-                index_buffer,
-                images: vec![Texture::empty()],
-            });
+    /// Draws the `--debug` overlays on top of the skeletons in the current pass.
+    fn draw_debug(&mut self, ctx: &mut Context, view: Mat4) {
+        if self.spines.iter().all(|spine| spine.debug.is_empty()) {
+            return;
         }
+        let white = *self
+            .debug_texture
+            .get_or_insert_with(|| Texture::from_rgba8(ctx, 1, 1, &[255, 255, 255, 255]));
+        let bindings = self.debug_bindings.get_or_insert_with(|| {
+            let mut bindings = create_bindings(ctx);
+            bindings.images = vec![white];
+            bindings
+        });
 
-        if self.render_png {
-            // --- on dessine dans notre RenderPass au lieu du default backbuffer ---
-            let pass_action = miniquad::PassAction::clear_color(0.1, 0.1, 0.1, 0.0);
-            ctx.begin_pass(self.render_pass, pass_action);
-            ctx.apply_pipeline(&self.pipeline);
-            ctx.set_cull_face(self.spine.cull_face);
-
-            let view = self.view();
-            for (renderable, bindings) in renderables.into_iter().zip(self.bindings.iter_mut()) {
-                let BlendStates {
-                    alpha_blend,
-                    color_blend,
-                } = renderable
-                    .blend_mode
-                    .get_blend_states(self.spine.controller.settings.premultiplied_alpha);
-                ctx.set_blend(Some(color_blend), Some(alpha_blend));
-                // Préparation des vertex/index buffers et des textures
-                let mut vertices = Vec::with_capacity(renderable.vertices.len());
-                for i in 0..renderable.vertices.len() {
-                    vertices.push(Vertex {
-                        position: Vec2 {
-                            x: renderable.vertices[i][0],
-                            y: renderable.vertices[i][1],
-                        },
-                        uv: Vec2 {
-                            x: renderable.uvs[i][0],
-                            y: renderable.uvs[i][1],
+        ctx.apply_pipeline(&self.pipeline);
+        ctx.set_cull_face(CullFace::Nothing);
+        let BlendStates {
+            alpha_blend,
+            color_blend,
+        } = BlendMode::Normal.get_blend_states(false);
+        ctx.set_blend(Some(color_blend), Some(alpha_blend));
+
+        let linear = self.color_space == ColorSpaceMode::Linear;
+        for spine in &self.spines {
+            // one output pixel, in skeleton units: the skeleton x axis goes through the world
+            // and view matrices to clip space, which spans the screen size over 2 units
+            let axis = (view * spine.world).x_axis.truncate().truncate();
+            let pixel = 1. / (axis * self.screen_size * 0.5).length();
+            let quads = build_overlay(&spine.controller.skeleton, spine.debug, pixel);
+            for batch in quads.chunks(MAX_MESH_INDICES / 6) {
+                let mut vertices = Vec::with_capacity(batch.len() * 4);
+                let mut indices = Vec::with_capacity(batch.len() * 6);
+                for quad in batch {
+                    let first = vertices.len() as u16;
+                    indices.extend([0, 1, 2, 0, 2, 3].map(|i| first + i));
+                    vertices.extend(quad.corners.map(|position| Vertex {
+                        position,
+                        uv: Vec2::ZERO,
+                        color: match linear {
+                            false => quad.color,
+                            true => quad.color.nonlinear_to_linear(),
                         },
-                        color: Color::from(renderable.colors[i]),
-                        dark_color: Color::from(renderable.dark_colors[i]),
-                    });
+                        dark_color: Color::new_rgba(0., 0., 0., 0.),
+                    }));
                 }
                 bindings.vertex_buffers[0].update(ctx, &vertices);
-                bindings.index_buffer.update(ctx, &renderable.indices);
-
-                // Si pas d'attachement (pas de texture), on skip ce renderable
-                let Some(attachment_renderer_object) = renderable.attachment_renderer_object else {
-                    continue;
-                };
-                let spine_texture =
-                    unsafe { &mut *(attachment_renderer_object as *mut SpineTexture) };
-                let texture = match spine_texture {
-                    SpineTexture::NeedsToBeLoaded {
-                        path,
-                        min_filter,
-                        mag_filter,
-                        x_wrap,
-                        y_wrap,
-                        format,
-                    } => {
-                        // on emprunte path au lieu de le déplacer
-                        let image = image::io::Reader::open(&*path)
-                            .unwrap_or_else(|_| panic!("failed to open image: {}", path))
-                            .decode()
-                            .unwrap_or_else(|_| panic!("failed to decode image: {}", path));
-                        // création de la texture GPU
-                        let params = TextureParams {
-                            width: image.width(),
-                            height: image.height(),
-                            format: *format,
-                            ..Default::default()
-                        };
-                        let tex = match format {
-                            TextureFormat::RGB8 => {
-                                Texture::from_data_and_format(ctx, &image.to_rgb8(), params)
-                            }
-                            TextureFormat::RGBA8 => {
-                                Texture::from_data_and_format(ctx, &image.to_rgba8(), params)
-                            }
-                            _ => unreachable!(),
-                        };
-                        tex.set_filter_min_mag(ctx, *min_filter, *mag_filter);
-                        tex.set_wrap_xy(ctx, *x_wrap, *y_wrap);
-                        // on remplace l'état pour ne plus recharger la prochaine frame
-                        *spine_texture = SpineTexture::Loaded(tex);
-                        // et on en extrait le handle (Texture est Copy)
-                        if let SpineTexture::Loaded(t) = spine_texture {
-                            *t
-                        } else {
-                            unreachable!()
-                        }
-                    }
-                    SpineTexture::Loaded(t) => *t,
-                };
-                bindings.images = vec![texture];
-
+                bindings.index_buffer.update(ctx, &indices);
                 ctx.apply_bindings(bindings);
                 ctx.apply_uniforms(&shader::Uniforms {
-                    world: self.spine.world,
+                    world: spine.world,
                     view,
+                    premultiplied: 0.,
                 });
-                ctx.draw(0, renderable.indices.len() as i32, 1);
+                ctx.draw(0, indices.len() as i32, 1);
             }
+        }
+    }
+}
+
+impl EventHandler for Stage {
+    fn update(&mut self, _ctx: &mut Context) {
+        let now = date::now();
+        if let Some(changed) = self.watcher.as_mut().and_then(|watcher| watcher.poll(now)) {
+            eprintln!("Changed: {}", changed.join(", "));
+            self.reload();
+        }
+        // une image écrite est rendue exactement au temps demandé
+        let dt = match self.render_png {
+            true => 0.,
+            false => ((now - self.last_frame_time) as f32).max(0.001),
+        };
+        let dt = self.viewer.playback_time(dt);
+        for spine in &mut self.spines {
+            spine.controller.update(dt, Physics::Update);
+            spine.apply_slot_overrides();
+        }
+        self.last_frame_time = now;
+    }
 
+    fn draw(&mut self, ctx: &mut Context) {
+        let view = self.view();
+        if self.render_png && self.rendered {
+            // --watch : rien à faire jusqu'au prochain changement
+            ctx.begin_default_pass(Default::default());
+            ctx.clear(Some((0.1, 0.1, 0.1, 1.0)), None, None);
             ctx.end_render_pass();
             ctx.commit_frame();
+        } else if self.render_png {
+            // les pages de l'atlas remplacé par un rebuild de --watch
+            self.delete_disposed_textures();
 
             // Lit le pixel buffer RGBA depuis la texture
             // width and height in *pixels* (after applying DPI scale)
             let w = (self.screen_size.x * ctx.dpi_scale()) as usize;
             let h = (self.screen_size.y * ctx.dpi_scale()) as usize;
+            let (frame, drawn_points) = self.render_frame(ctx, view, w, h);
+            ctx.commit_frame();
 
-            // allocate a buffer for RGBA8 bytes
-            let mut pixels = vec![0u8; w * h * 4];
-
-            // actually read them back from the GPU
-            self.color_texture.read_pixels(&mut pixels);
-
-            // flip vertically in-place
-            // flip vertically in-place
-            let line_bytes = w * 4;
-            for y in 0..(h / 2) {
-                let top_start = y * line_bytes;
-                let bot_start = (h - 1 - y) * line_bytes;
-
-                // split into [0 .. bot_start) and [bot_start .. end);
-                // these two slices are guaranteed non-overlapping
-                let (prefix, suffix) = pixels.split_at_mut(bot_start);
-
-                // within prefix, take the top row
-                let top_row = &mut prefix[top_start..top_start + line_bytes];
-                // within suffix, take the bottom row (which is at the start of suffix)
-                let bottom_row = &mut suffix[..line_bytes];
-
-                // now swap them
-                top_row.swap_with_slice(bottom_row);
+            let text = match self.output.encoding.metadata {
+                true => self
+                    .provenance()
+                    .unwrap_or_else(|err| panic!("failed to describe the render: {err:#}"))
+                    .text_chunks(),
+                false => vec![],
+            };
+            match self.output.png_path {
+                // `compare` sans --out
+                "none" => {}
+                path => {
+                    write_image(
+                        &frame,
+                        path,
+                        self.output.alpha,
+                        &self.output.encoding,
+                        &text,
+                    )
+                    .unwrap_or_else(|err| panic!("Impossible d’enregistrer l’image : {err:#}"));
+                    match path {
+                        "-" => eprintln!("✅ Première frame écrite sur stdout"),
+                        path => eprintln!("✅ Première frame écrite dans {path}"),
+                    }
+                }
             }
 
-            // then build your ImageBuffer and save as before
-            let img: ImageBuffer<Rgba<u8>, _> = ImageBuffer::from_raw(w as u32, h as u32, pixels)
-                .expect("Erreur création ImageBuffer");
-            img.save(self.png_path.clone())
-                .expect("Impossible d’enregistrer frame0.png");
-            println!("✅ Première frame écrite dans {}", self.png_path);
+            if let Some(legend_path) = self.output.slot_legend_path {
+                self.slot_legend
+                    .write_json(legend_path)
+                    .unwrap_or_else(|err| panic!("failed to write legend {legend_path}: {err}"));
+            }
+            if let Some(ids_path) = self.output.slot_ids_path {
+                self.slot_legend
+                    .write_ids(&frame.to_rgba8(self.output.alpha), ids_path)
+                    .unwrap_or_else(|err| panic!("failed to write slot IDs {ids_path}: {err}"));
+            }
 
-            exit(0);
-        } else {
-            // Delete textures that are no longer used. The delete call needs to happen here, before
-            // rendering, or it may not actually delete the texture.
-            for texture_delete in self.texture_delete_queue.lock().unwrap().drain(..) {
-                texture_delete.delete();
+            if let Some(report_path) = self.output.report_path {
+                self.report(view, w, h, drawn_points)
+                    .write(report_path)
+                    .unwrap_or_else(|err| panic!("failed to write report {report_path}: {err}"));
             }
 
-            // Begin frame
-            ctx.begin_default_pass(Default::default());
-            ctx.clear(Some((0.1, 0.1, 0.1, 0.0)), None, None);
-            ctx.apply_pipeline(&self.pipeline);
-
-            // Apply backface culling only if this skeleton needs it
-            ctx.set_cull_face(self.spine.cull_face);
-
-            let view = self.view();
-            for (renderable, bindings) in renderables.into_iter().zip(self.bindings.iter_mut()) {
-                // Set blend state based on this renderable's blend mode
-                let BlendStates {
-                    alpha_blend,
-                    color_blend,
-                } = renderable
-                    .blend_mode
-                    .get_blend_states(self.spine.controller.settings.premultiplied_alpha);
-                ctx.set_blend(Some(color_blend), Some(alpha_blend));
-
-                // Create the vertex and index buffers for miniquad
-                let mut vertices = vec![];
-                for vertex_index in 0..renderable.vertices.len() {
-                    vertices.push(Vertex {
-                        position: Vec2 {
-                            x: renderable.vertices[vertex_index][0],
-                            y: renderable.vertices[vertex_index][1],
-                        },
-                        uv: Vec2 {
-                            x: renderable.uvs[vertex_index][0],
-                            y: renderable.uvs[vertex_index][1],
-                        },
-                        color: Color::from(renderable.colors[vertex_index]),
-                        dark_color: Color::from(renderable.dark_colors[vertex_index]),
+            if self.output.hitmap_path.is_some() || self.output.hitmap_mask_path.is_some() {
+                let hitmap = HitMap::evaluate(
+                    self.spines
+                        .iter()
+                        .map(|spine| (&spine.controller.skeleton, view * spine.world)),
+                    w as u32,
+                    h as u32,
+                );
+                if let Some(hitmap_path) = self.output.hitmap_path {
+                    hitmap.write_json(hitmap_path).unwrap_or_else(|err| {
+                        panic!("failed to write hit map {hitmap_path}: {err}")
                     });
                 }
-                bindings.vertex_buffers[0].update(ctx, &vertices);
-                bindings.index_buffer.update(ctx, &renderable.indices);
-
-                // If there is no attachment (and therefore no texture), skip rendering this renderable
-                // May also be None if a create texture callback was never set.
-                let Some(attachment_renderer_object) = renderable.attachment_renderer_object else {
-                    continue;
-                };
-
-                // Load textures if they haven't been loaded already
-                let spine_texture =
-                    unsafe { &mut *(attachment_renderer_object as *mut SpineTexture) };
-                let texture = match spine_texture {
-                    SpineTexture::NeedsToBeLoaded {
-                        path,
-                        min_filter,
-                        mag_filter,
-                        x_wrap,
-                        y_wrap,
-                        format,
-                    } => {
-                        use image::io::Reader as ImageReader;
-
-                        #[allow(clippy::needless_borrows_for_generic_args)]
-                        let image = ImageReader::open(&path)
-                            .unwrap_or_else(|_| panic!("failed to open image: {}", &path))
-                            .decode()
-                            .unwrap_or_else(|_| panic!("failed to decode image: {}", &path));
-                        let texture_params = TextureParams {
-                            width: image.width(),
-                            height: image.height(),
-                            format: *format,
-                            ..Default::default()
-                        };
-                        let texture = match format {
-                            TextureFormat::RGB8 => {
-                                Texture::from_data_and_format(ctx, &image.to_rgb8(), texture_params)
-                            }
-                            TextureFormat::RGBA8 => Texture::from_data_and_format(
-                                ctx,
-                                &image.to_rgba8(),
-                                texture_params,
-                            ),
-                            _ => unreachable!(),
-                        };
-                        texture.set_filter_min_mag(ctx, *min_filter, *mag_filter);
-                        texture.set_wrap_xy(ctx, *x_wrap, *y_wrap);
-                        *spine_texture = SpineTexture::Loaded(texture);
-                        texture
-                    }
-                    SpineTexture::Loaded(texture) => *texture,
-                };
-                bindings.images = vec![texture];
+                if let Some(mask_path) = self.output.hitmap_mask_path {
+                    hitmap.write_mask(mask_path).unwrap_or_else(|err| {
+                        panic!("failed to write hit mask {mask_path}: {err}")
+                    });
+                }
+            }
 
-                // Draw this renderable
-                ctx.apply_bindings(bindings);
-                ctx.apply_uniforms(&shader::Uniforms {
-                    world: self.spine.world,
-                    view,
-                });
-                ctx.draw(0, renderable.indices.len() as i32, 1);
+            if let Some(comparison) = self.output.comparison {
+                let matches = comparison
+                    .run(&frame.to_rgba8(self.output.alpha))
+                    .unwrap_or_else(|err| panic!("failed to compare: {err:#}"));
+                exit(if matches { 0 } else { 1 });
+            }
+            if !self.output.watch {
+                exit(0);
             }
+            self.rendered = true;
+            eprintln!("Watching for changes…");
+        } else {
+            self.delete_disposed_textures();
+
+            // Begin frame
+            ctx.begin_default_pass(Default::default());
+            let clear_color = match self.background {
+                Some(_) => self.clear_color(),
+                None => (0.1, 0.1, 0.1, 0.0),
+            };
+            ctx.clear(Some(clear_color), None, None);
+            self.draw_canvas_image(ctx, self.background_image);
+            self.draw_skeletons(ctx, view);
+            self.draw_canvas_image(ctx, self.overlay_image);
+            self.draw_debug(ctx, view);
 
             // End frame
             ctx.end_render_pass();
@@ -508,11 +1620,95 @@ impl EventHandler for Stage {
     fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
         self.screen_size = Vec2::new(width, height) / ctx.dpi_scale();
     }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        keycode: KeyCode,
+        keymods: KeyMods,
+        repeat: bool,
+    ) {
+        // seuls les pas d'image se répètent quand la touche reste enfoncée
+        match self.viewer.key_down(keycode, keymods) {
+            Some(command @ Command::Seek(_)) => self.run_command(ctx, command),
+            Some(command) if !repeat => self.run_command(ctx, command),
+            _ => {}
+        }
+    }
+
+    fn mouse_button_down_event(&mut self, _ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+        self.viewer.mouse_down(button, x, y);
+    }
+
+    fn mouse_button_up_event(
+        &mut self,
+        _ctx: &mut Context,
+        _button: MouseButton,
+        _x: f32,
+        _y: f32,
+    ) {
+        self.viewer.mouse_up();
+    }
+
+    fn mouse_motion_event(&mut self, ctx: &mut Context, x: f32, y: f32) {
+        if let Some(command) = self.viewer.mouse_motion(x, y) {
+            self.run_command(ctx, command);
+        }
+    }
+
+    fn mouse_wheel_event(&mut self, ctx: &mut Context, _x: f32, y: f32) {
+        if let Some(command) = self.viewer.mouse_wheel(y) {
+            self.run_command(ctx, command);
+        }
+    }
+}
+
+/// Creates the render pass frames are drawn in, and its color texture read back from.
+fn create_render_target(ctx: &mut Context, width: u32, height: u32) -> (RenderPass, Texture) {
+    let color_texture = Texture::new_render_texture(
+        ctx,
+        TextureParams {
+            format: TextureFormat::RGBA8,
+            wrap: TextureWrap::Clamp,
+            filter: FilterMode::Nearest,
+            width,
+            height,
+        },
+    );
+    let depth_texture = Texture::new_render_texture(
+        ctx,
+        TextureParams {
+            format: TextureFormat::Depth,
+            width,
+            height,
+            ..Default::default()
+        },
+    );
+    let render_pass = RenderPass::new(ctx, color_texture, Some(depth_texture));
+    (render_pass, color_texture)
+}
+
+fn create_bindings(ctx: &mut Context) -> Bindings {
+    let vertex_buffer = Buffer::stream(
+        ctx,
+        BufferType::VertexBuffer,
+        MAX_MESH_VERTICES * std::mem::size_of::<Vertex>(),
+    );
+    let index_buffer = Buffer::stream(
+        ctx,
+        BufferType::IndexBuffer,
+        MAX_MESH_INDICES * std::mem::size_of::<u16>(),
+    );
+    Bindings {
+        vertex_buffers: vec![vertex_buffer],
+        index_buffer,
+        images: vec![Texture::empty()],
+    }
 }
 
-fn create_pipeline(ctx: &mut Context) -> Pipeline {
-    let shader = Shader::new(ctx, shader::VERTEX, shader::FRAGMENT, shader::meta())
-        .expect("failed to build shader");
+fn create_pipeline(ctx: &mut Context, fragment: &str) -> Pipeline {
+    let shader =
+        Shader::new(ctx, shader::VERTEX, fragment, shader::meta()).expect("failed to build shader");
     Pipeline::new(
         ctx,
         &[BufferLayout::default()],
@@ -653,14 +1849,101 @@ impl GetBlendStates for BlendMode {
 pub enum SpineTexture {
     NeedsToBeLoaded {
         path: String,
-        min_filter: FilterMode,
-        mag_filter: FilterMode,
+        min_filter: AtlasFilter,
+        mag_filter: AtlasFilter,
         x_wrap: TextureWrap,
         y_wrap: TextureWrap,
-        format: TextureFormat,
+        format: AtlasFormat,
     },
+    /// Page image read by [`Self::decode`], expanded to RGBA.
+    Decoded {
+        pixels: RgbaImage,
+        min_filter: AtlasFilter,
+        mag_filter: AtlasFilter,
+        x_wrap: TextureWrap,
+        y_wrap: TextureWrap,
+    },
+    /// Image replacing an atlas region, see [`replace_region`].
+    NeedsToBeUploaded(RgbaImage),
     Loaded(Texture),
 }
+
+impl SpineTexture {
+    /// Reads and decodes the page image, so that a missing or broken page fails the loading of
+    /// its atlas instead of the render drawing it.
+    fn decode(&mut self, assets: &dyn AssetResolver) -> anyhow::Result<()> {
+        let SpineTexture::NeedsToBeLoaded {
+            path,
+            min_filter,
+            mag_filter,
+            x_wrap,
+            y_wrap,
+            format,
+        } = self
+        else {
+            return Ok(());
+        };
+        let bytes = assets.read(path)?;
+        let image = image::load_from_memory(&bytes)
+            .with_context(|| format!("failed to decode image: {path}"))?;
+        // every page format is expanded to RGBA8, the only one miniquad mipmaps can use
+        *self = SpineTexture::Decoded {
+            pixels: texture::page_pixels(&image, *format),
+            min_filter: *min_filter,
+            mag_filter: *mag_filter,
+            x_wrap: *x_wrap,
+            y_wrap: *y_wrap,
+        };
+        Ok(())
+    }
+
+    /// Returns the GPU texture, uploading the page image on first use.
+    fn load(&mut self, ctx: &mut Context) -> Texture {
+        match self {
+            SpineTexture::NeedsToBeLoaded { path, .. } => {
+                panic!("atlas page drawn before being decoded: {path}")
+            }
+            SpineTexture::Decoded {
+                pixels,
+                min_filter,
+                mag_filter,
+                x_wrap,
+                y_wrap,
+            } => {
+                let texture_params = TextureParams {
+                    width: pixels.width(),
+                    height: pixels.height(),
+                    format: TextureFormat::RGBA8,
+                    ..Default::default()
+                };
+                let texture = Texture::from_data_and_format(ctx, pixels, texture_params);
+                texture.set_filter_min_mag(
+                    ctx,
+                    texture::filter_mode(*min_filter),
+                    texture::filter_mode(*mag_filter),
+                );
+                if let Some(mipmap_filter) = texture::mipmap_filter(*min_filter) {
+                    texture::upload_mipmaps(texture, pixels, mipmap_filter);
+                }
+                texture.set_wrap_xy(ctx, *x_wrap, *y_wrap);
+                *self = SpineTexture::Loaded(texture);
+                texture
+            }
+            SpineTexture::NeedsToBeUploaded(pixels) => {
+                let texture_params = TextureParams {
+                    width: pixels.width(),
+                    height: pixels.height(),
+                    format: TextureFormat::RGBA8,
+                    ..Default::default()
+                };
+                let texture = Texture::from_data_and_format(ctx, pixels, texture_params);
+                *self = SpineTexture::Loaded(texture);
+                texture
+            }
+            SpineTexture::Loaded(texture) => *texture,
+        }
+    }
+}
 mod shader {
     use glam::Mat4;
     use miniquad::*;
@@ -704,6 +1987,60 @@ mod shader {
         }
     "#;
 
+    /// Same as [`FRAGMENT`] for the linear color space: texels are linearized before being
+    /// tinted, vertex colors already are. `premultiplied` tells whether the page color has to be
+    /// divided by alpha first.
+    pub const LINEAR_FRAGMENT: &str = r#"
+        #version 100
+        precision mediump float;
+        varying lowp vec2 f_texcoord;
+        varying lowp vec4 f_color;
+        varying lowp vec4 f_dark_color;
+
+        uniform sampler2D tex;
+        uniform float premultiplied;
+
+        vec3 to_linear(vec3 color) {
+            return mix(
+                color / 12.92,
+                pow((color + 0.055) / 1.055, vec3(2.4)),
+                step(0.04045, color)
+            );
+        }
+
+        void main() {
+            vec4 tex_color = texture2D(tex, f_texcoord);
+            if (premultiplied > 0.5 && tex_color.a > 0.0) {
+                tex_color.rgb = to_linear(tex_color.rgb / tex_color.a) * tex_color.a;
+            } else {
+                tex_color.rgb = to_linear(tex_color.rgb);
+            }
+            gl_FragColor = vec4(
+                ((tex_color.a - 1.0) * f_dark_color.a + 1.0 - tex_color.rgb) * f_dark_color.rgb + tex_color.rgb * f_color.rgb,
+                tex_color.a * f_color.a
+            );
+        }
+    "#;
+
+    /// Writes the vertex color as is wherever the texture is mostly opaque, so that each slot
+    /// comes out as a flat, exact color.
+    pub const MASK_FRAGMENT: &str = r#"
+        #version 100
+        varying lowp vec2 f_texcoord;
+        varying lowp vec4 f_color;
+        varying lowp vec4 f_dark_color;
+
+        uniform sampler2D tex;
+
+        void main() {
+            lowp vec4 tex_color = texture2D(tex, f_texcoord);
+            if (tex_color.a * f_color.a < 0.5) {
+                discard;
+            }
+            gl_FragColor = vec4(f_color.rgb, 1.0);
+        }
+    "#;
+
     pub fn meta() -> ShaderMeta {
         ShaderMeta {
             images: vec!["tex".to_string()],
@@ -711,6 +2048,7 @@ mod shader {
                 uniforms: vec![
                     UniformDesc::new("world", UniformType::Mat4),
                     UniformDesc::new("view", UniformType::Mat4),
+                    UniformDesc::new("premultiplied", UniformType::Float1),
                 ],
             },
         }
@@ -720,5 +2058,7 @@ mod shader {
     pub struct Uniforms {
         pub world: Mat4,
         pub view: Mat4,
+        /// Only read by [`LINEAR_FRAGMENT`], 1 for premultiplied atlas pages.
+        pub premultiplied: f32,
     }
 }
@@ -0,0 +1,135 @@
+use image::{imageops, DynamicImage, RgbaImage};
+use miniquad::{gl, FilterMode, Texture};
+use rusty_spine::atlas::{AtlasFilter, AtlasFormat};
+
+/// Not exported by miniquad on every platform.
+const GL_TEXTURE_BINDING_2D: u32 = 0x8069;
+
+/// Expands a decoded page image to RGBA, keeping only what the atlas `format` would store on
+/// the GPU, so that pages render as they do in a runtime honoring the format.
+///
+/// Single channel formats follow the OpenGL conventions: `Alpha` samples as black with the
+/// image alpha, `Intensity` spreads the luminance over all four channels.
+pub fn page_pixels(image: &DynamicImage, format: AtlasFormat) -> RgbaImage {
+    match format {
+        AtlasFormat::Alpha => {
+            let mut pixels = image.to_rgba8();
+            for pixel in pixels.pixels_mut() {
+                pixel.0 = [0, 0, 0, pixel.0[3]];
+            }
+            pixels
+        }
+        AtlasFormat::Intensity => {
+            let luma = image.to_luma8();
+            RgbaImage::from_fn(luma.width(), luma.height(), |x, y| {
+                let [i] = luma.get_pixel(x, y).0;
+                [i, i, i, i].into()
+            })
+        }
+        AtlasFormat::LuminanceAlpha => {
+            let luma_alpha = image.to_luma_alpha8();
+            RgbaImage::from_fn(luma_alpha.width(), luma_alpha.height(), |x, y| {
+                let [l, a] = luma_alpha.get_pixel(x, y).0;
+                [l, l, l, a].into()
+            })
+        }
+        AtlasFormat::RGB565 => {
+            let mut pixels = image.to_rgba8();
+            for pixel in pixels.pixels_mut() {
+                let [r, g, b, _] = pixel.0;
+                pixel.0 = [quantize(r, 5), quantize(g, 6), quantize(b, 5), 255];
+            }
+            pixels
+        }
+        AtlasFormat::RGBA4444 => {
+            let mut pixels = image.to_rgba8();
+            for pixel in pixels.pixels_mut() {
+                pixel.0 = pixel.0.map(|channel| quantize(channel, 4));
+            }
+            pixels
+        }
+        AtlasFormat::RGB888 => {
+            let mut pixels = image.to_rgba8();
+            for pixel in pixels.pixels_mut() {
+                pixel.0[3] = 255;
+            }
+            pixels
+        }
+        AtlasFormat::RGBA8888 | AtlasFormat::UnknownFormat => image.to_rgba8(),
+    }
+}
+
+/// Rounds an 8-bit channel to `bits` bits, then expands it back the way the GPU does.
+fn quantize(value: u8, bits: u32) -> u8 {
+    let max = (1 << bits) - 1;
+    let quantized = (value as u32 * max + 127) / 255;
+    ((quantized * 255 + max / 2) / max) as u8
+}
+
+/// Filter used within a single mip level, which is all miniquad knows about.
+pub fn filter_mode(filter: AtlasFilter) -> FilterMode {
+    match filter {
+        AtlasFilter::Nearest
+        | AtlasFilter::MipmapNearestNearest
+        | AtlasFilter::MipmapNearestLinear => FilterMode::Nearest,
+        AtlasFilter::Linear
+        | AtlasFilter::Mipmap
+        | AtlasFilter::MipmapLinearNearest
+        | AtlasFilter::MipmapLinearLinear
+        | AtlasFilter::UnknownFilter => FilterMode::Linear,
+    }
+}
+
+/// OpenGL minification filter of the mipmapped atlas filters, `None` for the others.
+pub fn mipmap_filter(filter: AtlasFilter) -> Option<u32> {
+    match filter {
+        AtlasFilter::MipmapNearestNearest => Some(gl::GL_NEAREST_MIPMAP_NEAREST),
+        AtlasFilter::MipmapLinearNearest => Some(gl::GL_LINEAR_MIPMAP_NEAREST),
+        AtlasFilter::MipmapNearestLinear => Some(gl::GL_NEAREST_MIPMAP_LINEAR),
+        AtlasFilter::Mipmap | AtlasFilter::MipmapLinearLinear => Some(gl::GL_LINEAR_MIPMAP_LINEAR),
+        _ => None,
+    }
+}
+
+/// Uploads the full mip chain of `base` into `texture`, which must be an RGBA8 texture of the
+/// same size, and switches it to the `min_filter` mipmap filter.
+///
+/// miniquad has no mipmap support, so this goes through OpenGL directly and restores the
+/// texture binding it found.
+pub fn upload_mipmaps(texture: Texture, base: &RgbaImage, min_filter: u32) {
+    unsafe {
+        let mut previous_binding = 0;
+        gl::glGetIntegerv(GL_TEXTURE_BINDING_2D, &mut previous_binding);
+        gl::glBindTexture(gl::GL_TEXTURE_2D, texture.gl_internal_id());
+
+        let mut level = base.clone();
+        let mut index = 0;
+        while level.width() > 1 || level.height() > 1 {
+            level = imageops::resize(
+                &level,
+                (level.width() / 2).max(1),
+                (level.height() / 2).max(1),
+                imageops::FilterType::Triangle,
+            );
+            index += 1;
+            gl::glTexImage2D(
+                gl::GL_TEXTURE_2D,
+                index,
+                gl::GL_RGBA as i32,
+                level.width() as i32,
+                level.height() as i32,
+                0,
+                gl::GL_RGBA,
+                gl::GL_UNSIGNED_BYTE,
+                level.as_ptr() as *const _,
+            );
+        }
+        gl::glTexParameteri(
+            gl::GL_TEXTURE_2D,
+            gl::GL_TEXTURE_MIN_FILTER,
+            min_filter as i32,
+        );
+
+        gl::glBindTexture(gl::GL_TEXTURE_2D, previous_binding as u32);
+    }
+}
@@ -0,0 +1,142 @@
+use glam::Vec2;
+use miniquad::{KeyCode, KeyMods, MouseButton};
+
+/// Printed when the viewer opens and on `H`.
+pub const HELP: &str = "\
+Viewer controls:
+  Space            pause / resume
+  Left / Right     step one frame back / forward (Shift: 10 frames)
+  Up / Down        double / halve the playback speed, 0 to reset it
+  A / Shift+A      next / previous animation of the selected skeleton
+  S / Shift+S      next / previous skin of the selected skeleton
+  Tab              select the next skeleton of the scene
+  D                toggle the debug overlays
+  Left drag        pan the camera
+  Right drag       scrub the time
+  Wheel            zoom the camera
+  R                reset the camera
+  P                save the current frame as PNG
+  H                show this help";
+
+/// Duration of a frame step.
+pub const FRAME: f32 = 1. / 30.;
+/// Seconds scrubbed per pixel of right drag.
+const SCRUB_PER_PIXEL: f32 = 0.01;
+/// Zoom factor of a wheel notch.
+const ZOOM_STEP: f32 = 1.1;
+const MIN_SPEED: f32 = 1. / 16.;
+const MAX_SPEED: f32 = 16.;
+
+/// An action of the interactive viewer, from the keyboard or the mouse.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    TogglePause,
+    /// Moves the time of every skeleton by this many seconds, backwards when negative.
+    Seek(f32),
+    /// Multiplies the playback speed, 0 resetting it to 1.
+    Speed(f32),
+    /// Switches the selected skeleton to the next (1) or previous (-1) animation.
+    Animation(isize),
+    /// Switches the selected skeleton to the next (1) or previous (-1) skin.
+    Skin(isize),
+    NextSkeleton,
+    ToggleDebug,
+    /// Moves the camera by this many screen pixels, y down.
+    Pan(Vec2),
+    /// Multiplies the camera zoom.
+    Zoom(f32),
+    ResetCamera,
+    SaveFrame,
+    Help,
+}
+
+/// State of the window shown when no output file is given.
+#[derive(Debug)]
+pub struct Viewer {
+    pub paused: bool,
+    pub speed: f32,
+    /// Index of the skeleton animation and skin changes apply to.
+    pub selected: usize,
+    /// Number of frames saved with `P`, used to name the next one.
+    pub saved_frames: usize,
+    /// Button held and last position of the mouse, while dragging.
+    drag: Option<(MouseButton, Vec2)>,
+}
+
+impl Default for Viewer {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            speed: 1.,
+            selected: 0,
+            saved_frames: 0,
+            drag: None,
+        }
+    }
+}
+
+impl Viewer {
+    /// Seconds of animation for `dt` seconds of real time.
+    pub fn playback_time(&self, dt: f32) -> f32 {
+        match self.paused {
+            true => 0.,
+            false => dt * self.speed,
+        }
+    }
+
+    pub fn set_speed(&mut self, factor: f32) {
+        self.speed = match factor {
+            0. => 1.,
+            factor => (self.speed * factor).clamp(MIN_SPEED, MAX_SPEED),
+        };
+    }
+
+    pub fn key_down(&self, keycode: KeyCode, keymods: KeyMods) -> Option<Command> {
+        let direction = if keymods.shift { -1 } else { 1 };
+        let frames = if keymods.shift { 10. } else { 1. };
+        Some(match keycode {
+            KeyCode::Space => Command::TogglePause,
+            KeyCode::Right => Command::Seek(frames * FRAME),
+            KeyCode::Left => Command::Seek(-frames * FRAME),
+            KeyCode::Up => Command::Speed(2.),
+            KeyCode::Down => Command::Speed(0.5),
+            KeyCode::Key0 | KeyCode::Kp0 => Command::Speed(0.),
+            KeyCode::A => Command::Animation(direction),
+            KeyCode::S => Command::Skin(direction),
+            KeyCode::Tab => Command::NextSkeleton,
+            KeyCode::D => Command::ToggleDebug,
+            KeyCode::R => Command::ResetCamera,
+            KeyCode::P => Command::SaveFrame,
+            KeyCode::H | KeyCode::F1 => Command::Help,
+            _ => return None,
+        })
+    }
+
+    pub fn mouse_down(&mut self, button: MouseButton, x: f32, y: f32) {
+        self.drag = Some((button, Vec2::new(x, y)));
+    }
+
+    pub fn mouse_up(&mut self) {
+        self.drag = None;
+    }
+
+    pub fn mouse_motion(&mut self, x: f32, y: f32) -> Option<Command> {
+        let (button, last) = self.drag.as_mut()?;
+        let position = Vec2::new(x, y);
+        let delta = position - *last;
+        *last = position;
+        match button {
+            MouseButton::Left => Some(Command::Pan(delta)),
+            MouseButton::Right => Some(Command::Seek(delta.x * SCRUB_PER_PIXEL)),
+            _ => None,
+        }
+    }
+
+    pub fn mouse_wheel(&self, y: f32) -> Option<Command> {
+        match y {
+            y if y > 0. => Some(Command::Zoom(ZOOM_STEP)),
+            y if y < 0. => Some(Command::Zoom(1. / ZOOM_STEP)),
+            _ => None,
+        }
+    }
+}